@@ -0,0 +1,338 @@
+use axum::{
+    body::{to_bytes, Body},
+    extract::{ConnectInfo, FromRequestParts, MatchedPath, Request, State},
+    http::{header, request::Parts, HeaderMap, Method},
+    middleware::Next,
+    response::Response,
+};
+use hmac::{Hmac, Mac};
+use log::warn;
+use sha2::Sha256;
+use std::{
+    convert::Infallible,
+    net::SocketAddr,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    api::{errors::ApiError, AppState},
+    config::{AuthClient, AuthConfig, Role},
+    keep_alive::Lease,
+};
+
+pub const CLIENT_HEADER: &str = "x-recorder-client";
+pub const TIMESTAMP_HEADER: &str = "x-recorder-timestamp";
+pub const SIGNATURE_HEADER: &str = "x-recorder-signature";
+
+// Signed requests are buffered to be verified, anything bigger is rejected.
+const MAX_SIGNED_BODY_SIZE: usize = 1024 * 1024;
+
+// The client a request was authenticated as, available to the handlers as an extension.
+#[derive(Clone, Debug)]
+pub struct AuthenticatedClient(pub String);
+
+// Who is calling, the authenticated client or the client's ip address when auth is off.
+pub struct Caller {
+    pub name: String,
+    pub authenticated: bool,
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for Caller {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        if let Some(AuthenticatedClient(name)) = parts.extensions.get() {
+            return Ok(Caller {
+                name: name.clone(),
+                authenticated: true,
+            });
+        }
+        let address = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(address)| address.ip().to_string())
+            .unwrap_or_default();
+
+        Ok(Caller {
+            name: address,
+            authenticated: false,
+        })
+    }
+}
+
+impl Caller {
+    // Authenticated clients may only touch their own leases, otherwise knowing the id is enough.
+    pub fn owns(&self, lease: &Lease) -> bool {
+        !self.authenticated || lease.holder == self.name
+    }
+}
+
+// By the route the request matched. Read-only clients may only see the recorder's status and
+// which recordings there are, reading anything else tells about what was recorded, e.g. a
+// recording itself, the events, the metrics, the sessions or the jobs.
+fn required_role(method: &Method, route: &str) -> Role {
+    match (method, route) {
+        (&Method::GET | &Method::HEAD, "/status" | "/recordings") => Role::ReadOnly,
+        _ => Role::Operator,
+    }
+}
+
+pub async fn authorize(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(address): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let Some(auth) = &state.config.auth else {
+        return Ok(next.run(request).await);
+    };
+
+    let (mut parts, body) = request.into_parts();
+    let description = format!("{} {} from {address}", parts.method, parts.uri);
+
+    let (client, body) = if parts.headers.contains_key(SIGNATURE_HEADER) {
+        let body = to_bytes(body, MAX_SIGNED_BODY_SIZE)
+            .await
+            .map_err(|err| reject(&description, err.to_string()))?;
+        let client =
+            verify_signature(auth, &parts, &body).map_err(|reason| reject(&description, reason))?;
+        (client, Body::from(body))
+    } else {
+        let client =
+            verify_token(auth, &parts.headers).map_err(|reason| reject(&description, reason))?;
+        (client, body)
+    };
+
+    let route = parts
+        .extensions
+        .get::<MatchedPath>()
+        .map(|route| route.as_str())
+        .unwrap_or_default();
+    let required = required_role(&parts.method, route);
+    if client.role < required {
+        warn!(
+            "Rejected {description}, client {} is {:?} but {:?} is required",
+            client.name, client.role, required
+        );
+        return Err(ApiError::Forbidden);
+    }
+    parts
+        .extensions
+        .insert(AuthenticatedClient(client.name.clone()));
+
+    Ok(next.run(Request::from_parts(parts, body)).await)
+}
+
+fn reject(description: &str, reason: String) -> ApiError {
+    warn!("Rejected {description}, {reason}");
+    ApiError::Unauthorized
+}
+
+fn verify_token<'a>(auth: &'a AuthConfig, headers: &HeaderMap) -> Result<&'a AuthClient, String> {
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or("missing bearer token".to_string())?;
+
+    auth.clients
+        .iter()
+        .find(|client| {
+            client
+                .token
+                .as_ref()
+                .is_some_and(|expected| constant_time_eq(expected.as_bytes(), token.as_bytes()))
+        })
+        .ok_or("unknown bearer token".to_string())
+}
+
+// The signature is the hex HMAC-SHA256 of "{method}\n{path and query}\n{timestamp}\n{body}",
+// keyed with the client's secret. The timestamp is in unix seconds.
+fn verify_signature<'a>(
+    auth: &'a AuthConfig,
+    parts: &Parts,
+    body: &[u8],
+) -> Result<&'a AuthClient, String> {
+    let header = |name: &str| {
+        parts
+            .headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .ok_or(format!("missing {name} header"))
+    };
+    let name = header(CLIENT_HEADER)?;
+    let timestamp = header(TIMESTAMP_HEADER)?;
+    let signature = hex::decode(header(SIGNATURE_HEADER)?).or(Err("malformed signature"))?;
+
+    let client = auth
+        .clients
+        .iter()
+        .find(|client| client.name == name)
+        .ok_or(format!("unknown client {name}"))?;
+    let secret = client
+        .hmac_secret
+        .as_ref()
+        .ok_or(format!("client {name} can not sign requests"))?;
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let signed_at: u64 = timestamp.parse().or(Err("malformed timestamp"))?;
+    if now.abs_diff(signed_at) > auth.max_clock_skew_in_secs {
+        return Err(format!("signature of client {name} has expired"));
+    }
+
+    let path = parts
+        .uri
+        .path_and_query()
+        .map(|path| path.as_str())
+        .unwrap_or("/");
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).or(Err("invalid secret"))?;
+    mac.update(format!("{}\n{path}\n{timestamp}\n", parts.method).as_bytes());
+    mac.update(body);
+    mac.verify_slice(&signature)
+        .or(Err(format!("invalid signature from client {name}")))?;
+
+    Ok(client)
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{HeaderValue, Request};
+    use serde_json::json;
+
+    use super::*;
+
+    fn auth() -> AuthConfig {
+        serde_json::from_value(json!({
+            "clients": [
+                { "name": "dashboard", "role": "read_only", "token": "read-token" },
+                { "name": "operator", "role": "operator", "hmac_secret": "secret" }
+            ]
+        }))
+        .unwrap()
+    }
+
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+
+    // A request to POST /start, signed by `client` with `secret` at `timestamp`.
+    fn signed(client: &str, secret: &str, timestamp: u64, body: &str) -> Parts {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(format!("POST\n/start?fps=30\n{timestamp}\n{body}").as_bytes());
+        Request::post("/start?fps=30")
+            .header(CLIENT_HEADER, client)
+            .header(TIMESTAMP_HEADER, timestamp.to_string())
+            .header(SIGNATURE_HEADER, hex::encode(mac.finalize().into_bytes()))
+            .body(())
+            .unwrap()
+            .into_parts()
+            .0
+    }
+
+    #[test]
+    fn only_lets_read_only_clients_see_the_status_and_the_recordings_list() {
+        for (method, route) in [
+            (Method::GET, "/status"),
+            (Method::GET, "/recordings"),
+            (Method::HEAD, "/recordings"),
+        ] {
+            assert_eq!(
+                required_role(&method, route),
+                Role::ReadOnly,
+                "{method} {route}"
+            );
+        }
+        for (method, route) in [
+            (Method::GET, "/recordings/{name}"),
+            (Method::GET, "/events"),
+            (Method::GET, "/metrics"),
+            (Method::GET, "/sessions/{id}"),
+            (Method::GET, "/sessions/{id}/privacy_masks"),
+            (Method::GET, "/jobs/{id}"),
+            (Method::POST, "/start"),
+            (Method::POST, "/status"),
+            (Method::DELETE, "/recordings/{name}"),
+            (Method::GET, ""),
+        ] {
+            assert_eq!(
+                required_role(&method, route),
+                Role::Operator,
+                "{method} {route}"
+            );
+        }
+    }
+
+    #[test]
+    fn accepts_a_known_bearer_token() {
+        let auth = auth();
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_static("Bearer read-token"),
+        );
+        let client = verify_token(&auth, &headers).unwrap();
+        assert_eq!(client.name, "dashboard");
+        assert_eq!(client.role, Role::ReadOnly);
+    }
+
+    #[test]
+    fn rejects_a_missing_or_unknown_bearer_token() {
+        let auth = auth();
+        let mut headers = HeaderMap::new();
+        assert!(verify_token(&auth, &headers).is_err());
+        headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_static("read-token"),
+        );
+        assert!(verify_token(&auth, &headers).is_err());
+        headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_static("Bearer read-tokens"),
+        );
+        assert!(verify_token(&auth, &headers).is_err());
+        // A client that only signs has no token to match.
+        headers.insert(header::AUTHORIZATION, HeaderValue::from_static("Bearer "));
+        assert!(verify_token(&auth, &headers).is_err());
+    }
+
+    #[test]
+    fn accepts_a_signed_request() {
+        let auth = auth();
+        let parts = signed("operator", "secret", now(), "{}");
+        let client = verify_signature(&auth, &parts, b"{}").unwrap();
+        assert_eq!(client.name, "operator");
+    }
+
+    #[test]
+    fn rejects_a_tampered_or_expired_signature() {
+        let auth = auth();
+        let parts = signed("operator", "secret", now(), "{}");
+        assert!(verify_signature(&auth, &parts, b"{\"fps\":60}").is_err());
+        let parts = signed("operator", "wrong", now(), "{}");
+        assert!(verify_signature(&auth, &parts, b"{}").is_err());
+        let parts = signed("operator", "secret", now() - 301, "{}");
+        assert!(verify_signature(&auth, &parts, b"{}").is_err());
+        let parts = signed("operator", "secret", now() + 301, "{}");
+        assert!(verify_signature(&auth, &parts, b"{}").is_err());
+    }
+
+    #[test]
+    fn rejects_a_signature_from_a_client_without_a_secret() {
+        let auth = auth();
+        let parts = signed("dashboard", "", now(), "{}");
+        assert!(verify_signature(&auth, &parts, b"{}").is_err());
+        let parts = signed("unknown", "secret", now(), "{}");
+        assert!(verify_signature(&auth, &parts, b"{}").is_err());
+    }
+}
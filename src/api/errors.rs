@@ -1,9 +1,24 @@
-use axum::response::{IntoResponse, Response};
+use axum::{
+    http::header,
+    response::{IntoResponse, Response},
+};
 use reqwest::StatusCode;
 
 pub enum ApiError {
     CaptureAlreadyInProgress,
     NoCaptureIsRunning,
+    SessionNotFound(String),
+    JobNotFound(String),
+    LeaseNotFound(String),
+    BackendNotFound(String),
+    InvalidStartOptions(String),
+    InvalidPrivacyMasks(String),
+    InvalidSessionState(String),
+    RecordingNotFound(String),
+    RecordingInUse(String),
+    RangeNotSatisfiable(u64),
+    Unauthorized,
+    Forbidden,
     InternalServerError(String),
 }
 
@@ -16,8 +31,27 @@ impl From<anyhow::Error> for ApiError {
 impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
         match self {
-            ApiError::CaptureAlreadyInProgress => (StatusCode::TOO_EARLY, format!("Capture is already in progress")),
-            ApiError::NoCaptureIsRunning => (StatusCode::TOO_EARLY, format!("No capture is running")),
+            ApiError::CaptureAlreadyInProgress => (StatusCode::TOO_EARLY, "Capture is already in progress".to_string()),
+            ApiError::NoCaptureIsRunning => (StatusCode::TOO_EARLY, "No capture is running".to_string()),
+            ApiError::SessionNotFound(id) => (StatusCode::NOT_FOUND, format!("Session {id} was not found")),
+            ApiError::JobNotFound(id) => (StatusCode::NOT_FOUND, format!("Job {id} was not found")),
+            ApiError::LeaseNotFound(id) => (StatusCode::NOT_FOUND, format!("Lease {id} was not found")),
+            ApiError::BackendNotFound(name) => (StatusCode::NOT_FOUND, format!("Backend {name} is not available")),
+            ApiError::InvalidStartOptions(msg) => (StatusCode::BAD_REQUEST, format!("Invalid start options, {msg}")),
+            ApiError::InvalidPrivacyMasks(msg) => (StatusCode::BAD_REQUEST, format!("Invalid privacy masks, {msg}")),
+            ApiError::InvalidSessionState(msg) => (StatusCode::CONFLICT, msg),
+            ApiError::RecordingNotFound(name) => (StatusCode::NOT_FOUND, format!("Recording {name} was not found")),
+            ApiError::RecordingInUse(name) => (StatusCode::CONFLICT, format!("Recording {name} belongs to a session in progress")),
+            ApiError::Unauthorized => (StatusCode::UNAUTHORIZED, "Unauthorized".to_string()),
+            ApiError::Forbidden => (StatusCode::FORBIDDEN, "Forbidden".to_string()),
+            ApiError::RangeNotSatisfiable(length) => {
+                return (
+                    StatusCode::RANGE_NOT_SATISFIABLE,
+                    [(header::CONTENT_RANGE, format!("bytes */{length}"))],
+                    "Requested range is not satisfiable",
+                )
+                    .into_response()
+            }
             ApiError::InternalServerError(_msg) => (StatusCode::INTERNAL_SERVER_ERROR, format!("Internal server error, {_msg}")),
         }.into_response()
     }
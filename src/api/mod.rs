@@ -1,55 +1,113 @@
+pub mod auth;
 pub mod errors;
+pub mod recordings;
 
 use axum::{
-    extract::State,
-    response::Json,
+    extract::{Path, Query, State},
+    http::header,
+    middleware,
+    response::{
+        sse::{self, KeepAlive, Sse},
+        IntoResponse, Json,
+    },
     routing::{get, post},
     Router,
 };
-use chrono::Local;
-use log::warn;
+use log::{info, warn};
 use serde::{Deserialize, Serialize};
+use axum_server::tls_rustls::RustlsConfig;
+use chrono::Local;
+use reqwest::StatusCode;
 use serde_json::{json, Value};
+use tokio::task::JoinSet;
+use tokio_stream::{wrappers::BroadcastStream, Stream, StreamExt};
 use std::{
-    sync::{Arc, Mutex},
-    time::{Duration, SystemTime, UNIX_EPOCH},
-    u64,
+    convert::Infallible,
+    net::SocketAddr,
+    sync::Arc,
+    time::Duration,
 };
 
 use crate::{
-    api::errors::ApiError, audio, capture, config::Config, ffmpeg, keep_alive::keep_alive_task,
+    api::{auth::Caller, errors::ApiError},
+    audio,
+    capture::{
+        self, displays::DisplayInfo, BackendInfo, Backends, Capabilities, CaptureBackend,
+        CaptureStats,
+    },
+    config::{Config, LimitAction, MaskArea, PrivacyMask},
+    events::{EventBus, EventKind},
+    focus::focus_task,
+    jobs::{JobInfo, JobQueue},
+    keep_alive::{keep_alive_task, Lease},
+    metrics::{Gauges, Metrics},
+    session::{
+        options::{validate_privacy_masks, StartOptions},
+        Registry, Session, SessionInfo, SessionState,
+    },
+    uploader::uploader_task,
+    webhooks,
 };
 
 #[derive(Deserialize, Serialize)]
 pub struct Status {
     pub message: String,
-    pub recording: RecordingStatus,
-    pub last_keep_alive: u64,
+    pub state: SessionState,
+    pub session: Option<SessionInfo>,
+    // The leases holding the current session, see `keep_alive::Lease`.
+    pub leases: Vec<Lease>,
+    // The video capture of the current session, if it records video.
+    pub capture: Option<CaptureStats>,
 }
 
+// The response of POST /start, the session along with the starting client's lease.
 #[derive(Deserialize, Serialize)]
-pub struct RecordingStatus {
-    pub running: bool,
-    pub video: bool,
-    pub audio: bool,
+pub struct StartResponse {
+    #[serde(flatten)]
+    pub session: SessionInfo,
+    pub lease: Lease,
+}
+
+// The optional body of POST /keep_alive, without a lease id every lease of the caller is renewed.
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct KeepAliveOptions {
+    pub lease_id: Option<String>,
 }
 
 pub struct AppState {
-    pub recording: Arc<Mutex<bool>>,
-    pub recording_screen_raw: Arc<Mutex<bool>>,
-    pub recording_audio_raw: Arc<Mutex<bool>>,
-    pub last_keep_alive: Mutex<u64>,
-    pub filename: Mutex<String>,
+    pub sessions: Registry,
+    pub backends: Backends,
+    pub events: EventBus,
+    pub jobs: Arc<JobQueue>,
+    pub metrics: Arc<Metrics>,
     pub config: Config,
 }
 
 pub async fn start(config: Config) {
+    let events = EventBus::new();
+    webhooks::start(config.webhooks.clone(), events.clone());
+    crate::recordings::watch_uploads(config.recordings_folder.clone(), events.clone());
+    let metrics = Arc::new(Metrics::default());
+    let backends =
+        Backends::from_config(&config).expect("The capture backends should be valid");
+    // Every /start that does not pick another backend would be refused.
+    if config.capture.click_highlight.is_some()
+        && backends
+            .get(&config.capture.backend)
+            .is_some_and(|backend| !backend.capabilities().click_highlight)
+    {
+        warn!(
+            "capture.click_highlight is set, but backend {} can not highlight clicks",
+            config.capture.backend
+        );
+    }
     let shared_state = Arc::new(AppState {
-        recording: Arc::new(Mutex::new(false)),
-        recording_screen_raw: Arc::new(Mutex::new(false)),
-        recording_audio_raw: Arc::new(Mutex::new(false)),
-        last_keep_alive: Mutex::new(0),
-        filename: Mutex::new(format!("None")),
+        sessions: Registry::new(events.clone(), metrics.clone()),
+        backends,
+        events,
+        jobs: JobQueue::start(config.finalization.clone()),
+        metrics,
         config,
     });
 
@@ -58,101 +116,533 @@ pub async fn start(config: Config) {
         .route("/status", get(status))
         .route("/start", post(start_recording))
         .route("/stop", post(stop_recording))
+        .route("/pause", post(pause_recording))
+        .route("/resume", post(resume_recording))
         .route("/keep_alive", post(keep_alive))
+        .route("/events", get(stream_events))
+        .route("/recordings", get(recordings::list_recordings))
+        .route(
+            "/recordings/{name}",
+            get(recordings::download_recording).delete(recordings::delete_recording),
+        )
+        .route("/sessions/{id}", get(get_session))
+        .route("/sessions/{id}/stop", post(stop_session_by_id))
+        .route("/sessions/{id}/leases", post(join_session))
+        .route(
+            "/sessions/{id}/privacy_masks",
+            get(get_privacy_masks).put(set_privacy_masks),
+        )
+        .route("/jobs/{id}", get(get_job))
+        .route("/metrics", get(serve_metrics))
+        .route("/backends", get(list_backends))
+        .route("/displays", get(list_displays))
+        // Layered on the routes, so the role a request needs is decided by the route it matched.
+        .layer(middleware::from_fn_with_state(
+            shared_state.clone(),
+            auth::authorize,
+        ))
         .with_state(shared_state.clone());
 
+    if shared_state.config.auth.is_none() {
+        warn!("No auth is configured, the api is open to anyone who can reach it");
+    }
+
     keep_alive_task(shared_state.clone());
+    uploader_task(shared_state.clone());
+    focus_task(shared_state.clone());
 
-    // run our app with hyper, listening globally on port 3000
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3030").await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+    let server = &shared_state.config.server;
+    let tls = match &server.tls {
+        Some(tls) => Some({
+            // Only ring is built in, so this can not conflict with another provider.
+            let _ = rustls::crypto::ring::default_provider().install_default();
+
+            RustlsConfig::from_pem_file(&tls.cert_path, &tls.key_path)
+                .await
+                .expect("The TLS certificate and key should be present and valid")
+        }),
+        None => None,
+    };
+
+    // run our app with hyper, listening on every configured address
+    let mut servers = JoinSet::new();
+    for address in &server.addresses {
+        let address = SocketAddr::new(*address, server.port);
+        // Bound here rather than by the server, so a taken address fails the startup.
+        let listener = std::net::TcpListener::bind(address)
+            .unwrap_or_else(|err| panic!("Could not listen on {address}, {err}"));
+        let service = app
+            .clone()
+            .into_make_service_with_connect_info::<SocketAddr>();
+        match tls.clone() {
+            Some(tls) => {
+                servers.spawn(axum_server::from_tcp_rustls(listener, tls).serve(service));
+                info!("The Rust-Recorder is listening on https://{address}");
+            }
+            None => {
+                servers.spawn(axum_server::from_tcp(listener).serve(service));
+                info!("The Rust-Recorder is listening on http://{address}");
+            }
+        }
+    }
+
+    while let Some(result) = servers.join_next().await {
+        result.unwrap().expect("The api server has failed");
+    }
 }
 
 async fn status(State(state): State<Arc<AppState>>) -> Result<Json<Value>, ApiError> {
-    let recording: bool = *state.recording.lock().unwrap();
-    let recording_screen_raw: bool = *state.recording_screen_raw.lock().unwrap();
-    let recording_audio_raw = *state.recording_audio_raw.lock().unwrap();
+    let session = state.sessions.current();
     Ok(Json(json!(&Status {
         message: "Recorder is ok!".to_string(),
-        recording: RecordingStatus {
-            running: recording || recording_screen_raw || recording_audio_raw,
-            video: recording_screen_raw,
-            audio: recording_audio_raw
-        },
-        last_keep_alive: *state.last_keep_alive.lock().unwrap()
+        state: state.sessions.state(),
+        leases: session
+            .as_ref()
+            .map(|session| session.leases.list())
+            .unwrap_or_default(),
+        capture: session.as_ref().and_then(|session| {
+            let backend = video_backend(&state, session)?;
+            Some(backend.stats(session))
+        }),
+        session: session.map(|session| session.info()),
     })))
 }
 
-async fn start_recording(State(state): State<Arc<AppState>>) -> Result<String, ApiError> {
-    let recording: bool = *state.recording.lock().unwrap();
-    let recording_screen_raw: bool = *state.recording_screen_raw.lock().unwrap();
-    let recording_audio_raw = *state.recording_audio_raw.lock().unwrap();
-    match recording || recording_screen_raw || recording_audio_raw {
-        false => {
-            refresh_keep_alive(state.clone());
+async fn start_recording(
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    options: Option<Json<StartOptions>>,
+) -> Result<Json<StartResponse>, ApiError> {
+    let settings = options
+        .map(|Json(options)| options)
+        .unwrap_or_default()
+        .resolve(&state.config)
+        .map_err(|err| ApiError::InvalidStartOptions(err.to_string()))?;
+    let session = state
+        .sessions
+        .begin(&state.config.recordings_folder, settings)
+        .or(Err(ApiError::CaptureAlreadyInProgress))?;
+    let lease = session
+        .leases
+        .grant(caller.name, session.settings.keep_alive_timeout_in_secs);
 
-            let filename = format!(
-                "{}/{}",
-                state.config.recordings_folder,
-                Local::now().format("%d.%m.%Y-%H_%M_%S")
+    if session.settings.video {
+        let Some(backend) = video_backend(&state, &session) else {
+            let reason = format!(
+                "backend {} is not available on this build",
+                session.settings.capture.backend
+            );
+            session.fail(reason.clone());
+            return Err(ApiError::InvalidStartOptions(reason));
+        };
+        let capture_config = &session.settings.capture;
+        let capabilities = backend.capabilities();
+        let unsupported = if capture_config.window.is_some() && !capabilities.window {
+            Some("capture a window")
+        } else if capture_config.click_highlight.is_some() && !capabilities.click_highlight {
+            Some("highlight clicks")
+        } else if !can_mask(capabilities, &capture_config.privacy_masks) {
+            Some("mask windows")
+        } else {
+            None
+        };
+        if let Some(unsupported) = unsupported {
+            let reason = format!(
+                "backend {} can not {unsupported}",
+                capture_config.backend
             );
-            *state.filename.lock().unwrap() = filename.clone();
-            capture::capture_screen(
-                state.recording.clone(),
-                state.recording_screen_raw.clone(),
-                format!("{filename}.mp4"),
-                state.config.capture,
-            )?;
-            let audio_error = audio::record_audio(
-                state.recording.clone(),
-                state.recording_audio_raw.clone(),
-                format!("{filename}.wav"),
-            )
-            .err();
-            match audio_error {
-                Some(err) => warn!("Could not start audio recording! {:?}", err),
-                _ => (),
-            };
-
-            Ok(format!("Screen capture started"))
+            session.fail(reason.clone());
+            return Err(ApiError::InvalidStartOptions(reason));
         }
-        _ => Err(ApiError::CaptureAlreadyInProgress),
-    }
-}
-
-async fn stop_recording(State(state): State<Arc<AppState>>) -> Result<String, ApiError> {
-    let recording: bool = *state.recording.lock().unwrap();
-    let recording_screen_raw: bool = *state.recording_screen_raw.lock().unwrap();
-    let recording_audio_raw = *state.recording_audio_raw.lock().unwrap();
-    match recording || recording_screen_raw || recording_audio_raw {
-        true => {
-            *state.recording.lock().unwrap() = false;
-            loop {
-                tokio::time::sleep(Duration::from_millis(50)).await;
-                if !*state.recording_screen_raw.lock().unwrap()
-                    && !*state.recording_audio_raw.lock().unwrap()
-                {
-                    break;
-                }
+        if let Some(display) = capture_config
+            .display
+            .as_ref()
+            .filter(|_| capture_config.window.is_none())
+        {
+            let selected = backend
+                .displays()
+                .and_then(|displays| capture::displays::select(displays, Some(display)));
+            if let Err(err) = selected {
+                session.fail(err.to_string());
+                return Err(ApiError::InvalidStartOptions(err.to_string()));
+            }
+        }
+        if let Err(err) = backend.start(
+            session.clone(),
+            session.video_filename(),
+            session.settings.capture.clone(),
+        ) {
+            session.fail(err.to_string());
+            return Err(err.into());
+        }
+    }
+    if session.settings.audio {
+        let audio_error = audio::record_audio(
+            session.clone(),
+            session.audio_filename(),
+            state.config.audio,
+        )
+        .err();
+        if let Some(err) = audio_error {
+            if !session.settings.video {
+                session.fail(err.to_string());
+                return Err(err.into());
             }
+            warn!("Could not start audio recording! {:?}", err);
+        }
+    }
+
+    tokio::spawn(watch_session(state.clone(), session.clone()));
+    tokio::spawn(watch_limits(state.clone(), session.clone()));
+    session.transition(SessionState::Recording)?;
+    session.emit(EventKind::SessionStarted);
+    state.metrics.recordings_started.increment();
+
+    Ok(Json(StartResponse {
+        session: session.info(),
+        lease,
+    }))
+}
 
-            ffmpeg::combine_outputs(&*state.filename.lock().unwrap())?;
+async fn stop_recording(
+    State(state): State<Arc<AppState>>,
+) -> Result<(StatusCode, Json<JobInfo>), ApiError> {
+    match state.sessions.current() {
+        Some(session) => stop_session(&state, session),
+        None => Err(ApiError::NoCaptureIsRunning),
+    }
+}
 
-            Ok(format!("Capture stopped successfully"))
+async fn pause_recording(State(state): State<Arc<AppState>>) -> Result<Json<SessionInfo>, ApiError> {
+    let session = state.sessions.current().ok_or(ApiError::NoCaptureIsRunning)?;
+    let backend = video_backend(&state, &session);
+    if backend
+        .as_ref()
+        .is_some_and(|backend| !backend.capabilities().pause)
+    {
+        return Err(ApiError::InvalidSessionState(format!(
+            "Backend {} can not pause",
+            session.settings.capture.backend
+        )));
+    }
+    session
+        .transition(SessionState::Paused)
+        .map_err(|err| ApiError::InvalidSessionState(err.to_string()))?;
+    if let Some(backend) = backend {
+        backend.pause(&session, true)?;
+    }
+    session.emit(EventKind::SessionPaused);
+    info!("Session {} paused", session.id);
+
+    Ok(Json(session.info()))
+}
+
+async fn resume_recording(
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+) -> Result<Json<SessionInfo>, ApiError> {
+    let session = state.sessions.current().ok_or(ApiError::NoCaptureIsRunning)?;
+    session
+        .transition(SessionState::Recording)
+        .map_err(|err| ApiError::InvalidSessionState(err.to_string()))?;
+    if let Some(backend) = video_backend(&state, &session) {
+        backend.pause(&session, false)?;
+    }
+    // A session paused by the keep-alive policy would otherwise expire again right away.
+    session.leases.renew_held_by(&caller.name);
+    session.emit(EventKind::SessionResumed);
+    info!("Session {} resumed", session.id);
+
+    Ok(Json(session.info()))
+}
+
+// The backend capturing the session's video, if it records video.
+fn video_backend(state: &AppState, session: &Session) -> Option<Arc<dyn CaptureBackend>> {
+    if !session.settings.video {
+        return None;
+    }
+    state.backends.get(&session.settings.capture.backend)
+}
+
+// Whether the backend can hide every one of the masks.
+fn can_mask(capabilities: Capabilities, masks: &[PrivacyMask]) -> bool {
+    capabilities.window_masks
+        || !masks
+            .iter()
+            .any(|mask| matches!(mask.area, MaskArea::Window { .. }))
+}
+
+async fn list_backends(State(state): State<Arc<AppState>>) -> Json<Vec<BackendInfo>> {
+    Json(state.backends.list())
+}
+
+#[derive(Deserialize, Debug)]
+pub struct DisplaysQuery {
+    // Defaults to the configured backend.
+    pub backend: Option<String>,
+}
+
+async fn list_displays(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<DisplaysQuery>,
+) -> Result<Json<Vec<DisplayInfo>>, ApiError> {
+    let name = query
+        .backend
+        .unwrap_or_else(|| state.config.capture.backend.clone());
+    let backend = state
+        .backends
+        .get(&name)
+        .ok_or(ApiError::BackendNotFound(name))?;
+
+    Ok(Json(backend.displays()?))
+}
+
+async fn get_session(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Json<SessionInfo>, ApiError> {
+    match state.sessions.get(&id) {
+        Some(session) => Ok(Json(session.info())),
+        None => Err(ApiError::SessionNotFound(id)),
+    }
+}
+
+// Takes a lease on a running session, so it keeps recording as long as this client is alive too.
+async fn join_session(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    caller: Caller,
+) -> Result<Json<Lease>, ApiError> {
+    let session = state
+        .sessions
+        .get(&id)
+        .ok_or(ApiError::SessionNotFound(id))?;
+    if !session.is_capturing() {
+        return Err(ApiError::InvalidSessionState(format!(
+            "Session {} is {:?}, only a capturing session can be joined",
+            session.id,
+            session.state()
+        )));
+    }
+    let lease = session
+        .leases
+        .grant(caller.name, session.settings.keep_alive_timeout_in_secs);
+    info!("{} joined session {}", lease.holder, session.id);
+
+    Ok(Json(lease))
+}
+
+async fn get_privacy_masks(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<PrivacyMask>>, ApiError> {
+    match state.sessions.get(&id) {
+        Some(session) => Ok(Json(session.privacy_masks())),
+        None => Err(ApiError::SessionNotFound(id)),
+    }
+}
+
+// Replaces the masks of a capturing session. The native and synthetic captures hide the new ones
+// from their next frames, the ffmpeg ones black out the video until they restarted with them.
+async fn set_privacy_masks(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Json(masks): Json<Vec<PrivacyMask>>,
+) -> Result<Json<SessionInfo>, ApiError> {
+    let session = state
+        .sessions
+        .get(&id)
+        .ok_or(ApiError::SessionNotFound(id))?;
+    if !session.is_capturing() {
+        return Err(ApiError::InvalidSessionState(format!(
+            "Session {} is {:?}, only a capturing session can change its privacy masks",
+            session.id,
+            session.state()
+        )));
+    }
+    validate_privacy_masks(&masks)
+        .map_err(|err| ApiError::InvalidPrivacyMasks(err.to_string()))?;
+    if let Some(backend) = video_backend(&state, &session) {
+        if !can_mask(backend.capabilities(), &masks) {
+            return Err(ApiError::InvalidPrivacyMasks(format!(
+                "backend {} can not mask windows",
+                session.settings.capture.backend
+            )));
         }
-        _ => Err(ApiError::NoCaptureIsRunning),
     }
+
+    let count = masks.len();
+    session.set_privacy_masks(masks);
+    session.emit(EventKind::PrivacyMasksChanged { masks: count });
+    info!("Session {} now hides {count} privacy masks", session.id);
+
+    Ok(Json(session.info()))
 }
 
-async fn keep_alive(State(state): State<Arc<AppState>>) -> Result<String, ApiError> {
-    refresh_keep_alive(state);
+async fn stop_session_by_id(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<(StatusCode, Json<JobInfo>), ApiError> {
+    match state.sessions.get(&id) {
+        Some(session) => stop_session(&state, session),
+        None => Err(ApiError::SessionNotFound(id)),
+    }
+}
+
+// Only asks the session to stop, the finalization job reports when its outputs are ready.
+fn stop_session(
+    state: &AppState,
+    session: Arc<Session>,
+) -> Result<(StatusCode, Json<JobInfo>), ApiError> {
+    if !session.request_stop() && session.state() != SessionState::Stopping {
+        return Err(ApiError::NoCaptureIsRunning);
+    }
+    if let Some(backend) = video_backend(state, &session) {
+        backend.stop(&session);
+    }
+    let job = state.jobs.finalize(session);
+
+    Ok((StatusCode::ACCEPTED, Json(job.info())))
+}
 
-    Ok(format!("Ok"))
+// Queues the finalization once the session is stopped, by /stop, the keep-alive task or the
+// capture itself.
+async fn watch_session(state: Arc<AppState>, session: Arc<Session>) {
+    loop {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        match session.state() {
+            SessionState::Stopping => break,
+            SessionState::Failed => return,
+            _ => (),
+        }
+    }
+
+    state.jobs.finalize(session);
 }
 
-fn refresh_keep_alive(state: Arc<AppState>) {
-    *state.last_keep_alive.lock().unwrap() = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_secs();
+// Ends or rotates the session once its current segment reaches one of the session limits.
+async fn watch_limits(state: Arc<AppState>, session: Arc<Session>) {
+    let limits = session.settings.limits;
+    if limits.max_duration_in_secs.is_none() && limits.max_size_in_bytes.is_none() {
+        return;
+    }
+    // A backend that can not rotate ends the session instead.
+    let on_limit = match video_backend(&state, &session) {
+        Some(backend) if !backend.capabilities().rotation => LimitAction::Stop,
+        _ => limits.on_limit,
+    };
+
+    let mut interval = tokio::time::interval(Duration::from_secs(1));
+    while session.is_capturing() {
+        interval.tick().await;
+        if session.is_paused() {
+            continue;
+        }
+
+        let reason = if limits
+            .max_duration_in_secs
+            .is_some_and(|max| session.segment_duration().as_secs() >= max)
+        {
+            "maximum duration"
+        } else if limits
+            .max_size_in_bytes
+            .is_some_and(|max| session.segment_size() >= max)
+        {
+            "maximum size"
+        } else {
+            continue;
+        };
+
+        match on_limit {
+            LimitAction::Stop => {
+                session.emit(EventKind::LimitReached {
+                    reason: reason.to_string(),
+                });
+                if session.request_stop() {
+                    info!("Session {} reached its {reason}, stopping", session.id);
+                }
+            }
+            LimitAction::Rotate => {
+                if let Some(segment) = session.rotate() {
+                    info!(
+                        "Session {} reached its {reason}, rotating to segment {}",
+                        session.id,
+                        segment + 1
+                    );
+                    state.jobs.finalize_segment(session.clone(), segment);
+                }
+            }
+        }
+    }
+}
+
+async fn get_job(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Json<JobInfo>, ApiError> {
+    match state.jobs.get(&id) {
+        Some(job) => Ok(Json(job.info())),
+        None => Err(ApiError::JobNotFound(id)),
+    }
+}
+
+// Streams every recorder event as Server-Sent Events, the event name is its type.
+async fn stream_events(
+    State(state): State<Arc<AppState>>,
+) -> Sse<impl Stream<Item = Result<sse::Event, Infallible>>> {
+    let stream = BroadcastStream::new(state.events.subscribe()).filter_map(|event| {
+        let event = serde_json::to_value(event.ok()?).ok()?;
+        let name = event["type"].as_str().unwrap_or_default().to_string();
+        Some(Ok(sse::Event::default().event(name).data(event.to_string())))
+    });
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+// Serves the counters in the Prometheus text format.
+async fn serve_metrics(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let session_duration = state
+        .sessions
+        .current()
+        .filter(|session| session.is_capturing())
+        .and_then(|session| (Local::now() - session.started_at).to_std().ok())
+        .unwrap_or_default();
+    let gauges = Gauges {
+        session_duration,
+        free_disk_space: fs2::available_space(&state.config.recordings_folder).ok(),
+    };
+
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.metrics.render(&gauges),
+    )
+}
+
+// Renews the caller's leases on the current session, returning the renewed leases.
+async fn keep_alive(
+    State(state): State<Arc<AppState>>,
+    caller: Caller,
+    options: Option<Json<KeepAliveOptions>>,
+) -> Result<Json<Vec<Lease>>, ApiError> {
+    let Some(session) = state.sessions.current() else {
+        return Ok(Json(vec![]));
+    };
+    let options = options.map(|Json(options)| options).unwrap_or_default();
+
+    match options.lease_id {
+        Some(id) => {
+            let lease = session
+                .leases
+                .get(&id)
+                .filter(|lease| caller.owns(lease))
+                .ok_or(ApiError::LeaseNotFound(id.clone()))?;
+            let lease = session
+                .leases
+                .renew(&lease.id)
+                .ok_or(ApiError::LeaseNotFound(id))?;
+
+            Ok(Json(vec![lease]))
+        }
+        None => Ok(Json(session.leases.renew_held_by(&caller.name))),
+    }
 }
@@ -0,0 +1,137 @@
+use axum::{
+    body::Body,
+    extract::{Path, State},
+    http::{header, HeaderMap, HeaderValue},
+    response::{IntoResponse, Json, Response},
+};
+use log::info;
+use reqwest::StatusCode;
+use std::{io::SeekFrom, path::PathBuf, sync::Arc};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio_util::io::ReaderStream;
+
+use crate::{
+    api::{errors::ApiError, AppState},
+    recordings::{self, Recording},
+};
+
+pub async fn list_recordings(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<Recording>>, ApiError> {
+    let folder = state.config.recordings_folder.clone();
+    Ok(Json(blocking(move || recordings::list(&folder)).await??))
+}
+
+// Serves a recording, honoring a single `Range` so players can stream and seek.
+pub async fn download_recording(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let path = resolve(&state, &name).await?;
+    let mut file = tokio::fs::File::open(&path)
+        .await
+        .map_err(|err| ApiError::InternalServerError(err.to_string()))?;
+    let length = file
+        .metadata()
+        .await
+        .map_err(|err| ApiError::InternalServerError(err.to_string()))?
+        .len();
+
+    let range = match headers
+        .get(header::RANGE)
+        .and_then(|range| range.to_str().ok())
+    {
+        Some(range) => {
+            Some(parse_range(range, length).ok_or(ApiError::RangeNotSatisfiable(length))?)
+        }
+        None => None,
+    };
+
+    let mut response_headers = HeaderMap::new();
+    response_headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static(recordings::content_type(&path)),
+    );
+    response_headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+
+    let (status, start, end) = match range {
+        Some((start, end)) => {
+            response_headers.insert(
+                header::CONTENT_RANGE,
+                HeaderValue::from_str(&format!("bytes {start}-{end}/{length}")).unwrap(),
+            );
+            (StatusCode::PARTIAL_CONTENT, start, end)
+        }
+        None => (StatusCode::OK, 0, length.saturating_sub(1)),
+    };
+    let content_length = if length == 0 { 0 } else { end - start + 1 };
+    response_headers.insert(header::CONTENT_LENGTH, HeaderValue::from(content_length));
+
+    file.seek(SeekFrom::Start(start))
+        .await
+        .map_err(|err| ApiError::InternalServerError(err.to_string()))?;
+    let body = Body::from_stream(ReaderStream::new(file.take(content_length)));
+
+    Ok((status, response_headers, body).into_response())
+}
+
+pub async fn delete_recording(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+) -> Result<String, ApiError> {
+    let path = resolve(&state, &name).await?;
+
+    if state
+        .sessions
+        .in_progress()
+        .iter()
+        .any(|session| session.owns(&path))
+    {
+        return Err(ApiError::RecordingInUse(name));
+    }
+
+    blocking(move || recordings::remove(&path)).await??;
+    info!("Recording {name} was deleted");
+
+    Ok("Ok".to_string())
+}
+
+async fn resolve(state: &AppState, name: &str) -> Result<PathBuf, ApiError> {
+    let (folder, name) = (state.config.recordings_folder.clone(), name.to_string());
+    blocking(move || recordings::resolve(&folder, &name).or(Err(ApiError::RecordingNotFound(name))))
+        .await?
+}
+
+// Runs the file system work off the runtime's threads, inspecting a recording reads through it.
+async fn blocking<T: Send + 'static>(
+    work: impl FnOnce() -> T + Send + 'static,
+) -> Result<T, ApiError> {
+    tokio::task::spawn_blocking(work)
+        .await
+        .map_err(|err| ApiError::InternalServerError(err.to_string()))
+}
+
+// Parses a single `bytes=` range into inclusive offsets, None when it can not be satisfied.
+fn parse_range(range: &str, length: u64) -> Option<(u64, u64)> {
+    let (start, end) = range.strip_prefix("bytes=")?.split_once('-')?;
+    if length == 0 || range.contains(',') {
+        return None;
+    }
+    let (start, end) = match (start.trim(), end.trim()) {
+        ("", suffix) => {
+            let suffix: u64 = suffix.parse().ok()?;
+            if suffix == 0 {
+                return None;
+            }
+            (length.saturating_sub(suffix), length - 1)
+        }
+        (start, "") => (start.parse().ok()?, length - 1),
+        (start, end) => (
+            start.parse().ok()?,
+            end.parse::<u64>().ok()?.min(length - 1),
+        ),
+    };
+
+    (start <= end && start < length).then_some((start, end))
+}
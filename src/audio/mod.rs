@@ -11,11 +11,25 @@ use cpal::{
 };
 use log::{error, info};
 
+use crate::{config::AudioSource, events::EventKind, session::Session};
+
+mod synthetic;
+
 pub fn record_audio(
-    recording: Arc<Mutex<bool>>,
-    recording_raw: Arc<Mutex<bool>>,
+    session: Arc<Session>,
     filename: String,
+    source: AudioSource,
 ) -> Result<(), anyhow::Error> {
+    match source {
+        AudioSource::Device => record_device(session, filename),
+        AudioSource::Sine { frequency_in_hz } => {
+            synthetic::record_generated(session, filename, frequency_in_hz)
+        }
+        AudioSource::Silence => synthetic::record_generated(session, filename, 0.0),
+    }
+}
+
+fn record_device(session: Arc<Session>, filename: String) -> Result<(), anyhow::Error> {
     let host = cpal::default_host();
     let device = host
         .default_input_device()
@@ -36,7 +50,11 @@ pub fn record_audio(
 
     let writer = hound::WavWriter::create(filename, spec)?;
 
-    let writer = Arc::new(Mutex::new(writer));
+    let writer = Arc::new(Mutex::new(AudioOutput {
+        writer,
+        spec,
+        segment: session.segment(),
+    }));
 
     let err_fn = move |err| {
         error!("An error occurred on audio stream: {}", err);
@@ -44,30 +62,30 @@ pub fn record_audio(
 
     let writer_ptr = writer.clone();
 
-    let recording_raw_ptr = recording_raw.clone();
-    
+    let session_ptr = session.clone();
+
     let stream = match config.sample_format() {
         cpal::SampleFormat::I8 => device.build_input_stream(
             &config.into(),
-            move |data, _: &_| write_input_data::<i8, i8>(&recording_raw_ptr, data, &writer_ptr),
+            move |data, _: &_| write_input_data::<i8, i8>(&session_ptr, data, &writer_ptr),
             err_fn,
             None,
         )?,
         cpal::SampleFormat::I16 => device.build_input_stream(
             &config.into(),
-            move |data, _: &_| write_input_data::<i16, i16>(&recording_raw_ptr, data, &writer_ptr),
+            move |data, _: &_| write_input_data::<i16, i16>(&session_ptr, data, &writer_ptr),
             err_fn,
             None,
         )?,
         cpal::SampleFormat::I32 => device.build_input_stream(
             &config.into(),
-            move |data, _: &_| write_input_data::<i32, i32>(&recording_raw_ptr, data, &writer_ptr),
+            move |data, _: &_| write_input_data::<i32, i32>(&session_ptr, data, &writer_ptr),
             err_fn,
             None,
         )?,
         cpal::SampleFormat::F32 => device.build_input_stream(
             &config.into(),
-            move |data, _: &_| write_input_data::<f32, f32>(&recording_raw_ptr, data, &writer_ptr),
+            move |data, _: &_| write_input_data::<f32, f32>(&session_ptr, data, &writer_ptr),
             err_fn,
             None,
         )?,
@@ -78,41 +96,89 @@ pub fn record_audio(
         }
     };
 
-    info!(
-        "Starting audio recording on device: {}",
-        device.name().unwrap_or("None".into())
-    );
+    let device_name = device.name().unwrap_or("None".into());
+    info!("Starting audio recording on device: {}", device_name);
     stream.play()?;
-    *recording_raw.lock().unwrap() = true;
+    session.set_audio_running(true);
+    session.emit(EventKind::AudioDeviceOpened {
+        device: device_name,
+    });
 
     thread::spawn(move || {
         loop {
-            if !*recording.lock().unwrap() {
+            if !session.is_capturing() || session.is_terminated() {
                 break;
             }
             thread::sleep(time::Duration::from_millis(100));
         }
-        *recording.lock().unwrap() = false;
         drop(stream);
-        *recording_raw.lock().unwrap() = false;
+        session.set_audio_running(false);
         info!("Audio recording stopped");
     });
 
     Ok(())
 }
 
-type WavWriterHandle = Arc<Mutex<hound::WavWriter<BufWriter<File>>>>;
+// The wav output of the session's current segment.
+struct AudioOutput {
+    writer: hound::WavWriter<BufWriter<File>>,
+    spec: hound::WavSpec,
+    segment: usize,
+}
+
+impl AudioOutput {
+    // Finishes the current wav and starts the next one once the session rotated.
+    fn follow_segment(&mut self, session: &Session) {
+        let segment = session.segment();
+        if segment == self.segment {
+            return;
+        }
+
+        let filename = format!("{}.wav", session.segment_filename(segment));
+        match hound::WavWriter::create(&filename, self.spec) {
+            Ok(writer) => {
+                let previous = std::mem::replace(&mut self.writer, writer);
+                if let Err(err) = previous.finalize() {
+                    error!("Could not finalize the audio of segment {}, {err}", self.segment);
+                }
+                self.segment = segment;
+                session.set_audio_segment(segment);
+                info!("Audio recording moved to {filename}");
+            }
+            Err(err) => error!("Could not create {filename}, {err}"),
+        }
+    }
+}
+
+type AudioOutputHandle = Arc<Mutex<AudioOutput>>;
 
-fn write_input_data<T, U>(recording: &Arc<Mutex<bool>>, input: &[T], writer: &WavWriterHandle)
+fn write_input_data<T, U>(session: &Arc<Session>, input: &[T], writer: &AudioOutputHandle)
 where
     T: Sample,
     U: Sample + hound::Sample + FromSample<T>,
 {
-    *recording.lock().unwrap() = true;
-    if let Ok(mut writer) = writer.try_lock() {
-        for &sample in input.iter() {
-            let sample: U = U::from_sample(sample);
-            writer.write_sample(sample).ok();
+    // Finalizing may have given up on the audio, then its output is left alone.
+    if !session.is_capturing() || session.is_terminated() {
+        return;
+    }
+    let metrics = session.metrics();
+    match writer.try_lock() {
+        Ok(mut output) => {
+            output.follow_segment(session);
+            // Skipping the samples while paused keeps the audio aligned with the video, which
+            // drops its paused frames.
+            if session.is_paused() {
+                return;
+            }
+            let writer = &mut output.writer;
+            for &sample in input.iter() {
+                let sample: U = U::from_sample(sample);
+                match writer.write_sample(sample) {
+                    Ok(()) => metrics.audio_samples_written.increment(),
+                    Err(_) => metrics.audio_samples_dropped.increment(),
+                }
+            }
         }
+        Err(_) => metrics.audio_samples_dropped.add(input.len() as u64),
     }
 }
@@ -0,0 +1,66 @@
+use std::{
+    f32::consts::TAU,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use log::info;
+
+use super::{write_input_data, AudioOutput};
+use crate::{events::EventKind, session::Session};
+
+const SAMPLE_RATE: u32 = 48_000;
+const AMPLITUDE: f32 = 0.25;
+
+// Records a generated mono tone instead of an input device, silence when the frequency is 0.
+// Samples are generated for the time that passed, so the audio keeps pace with the video.
+pub fn record_generated(
+    session: Arc<Session>,
+    filename: String,
+    frequency_in_hz: f32,
+) -> Result<(), anyhow::Error> {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: SAMPLE_RATE,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let writer = Arc::new(Mutex::new(AudioOutput {
+        writer: hound::WavWriter::create(filename, spec)?,
+        spec,
+        segment: session.segment(),
+    }));
+
+    let device = match frequency_in_hz > 0.0 {
+        true => format!("synthetic {frequency_in_hz} Hz tone"),
+        false => "synthetic silence".to_string(),
+    };
+    info!("Starting audio recording on device: {}", device);
+    session.set_audio_running(true);
+    session.emit(EventKind::AudioDeviceOpened { device });
+
+    thread::spawn(move || {
+        let started = Instant::now();
+        let mut generated: u64 = 0;
+        let mut samples = vec![];
+
+        while session.is_capturing() {
+            thread::sleep(Duration::from_millis(100));
+            let due = (started.elapsed().as_secs_f64() * SAMPLE_RATE as f64) as u64;
+            samples.clear();
+            samples.extend((generated..due).map(|index| {
+                let phase = (index as f64 * frequency_in_hz as f64 / SAMPLE_RATE as f64).fract();
+                ((TAU * phase as f32).sin() * AMPLITUDE * i16::MAX as f32) as i16
+            }));
+            generated = due;
+            write_input_data::<i16, i16>(&session, &samples, &writer);
+        }
+
+        drop(writer);
+        session.set_audio_running(false);
+        info!("Audio recording stopped");
+    });
+
+    Ok(())
+}
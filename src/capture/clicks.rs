@@ -0,0 +1,107 @@
+use std::time::Duration;
+
+use super::mouse::MouseTracker;
+use crate::config::ClickHighlight;
+
+// The ring around the cursor between the clicks.
+const CURSOR_OPACITY: f32 = 0.35;
+
+// Draws the mouse clicks into BGRA frames on the cpu, as rings that fade out.
+#[derive(Debug, Clone)]
+pub struct ClickOverlay {
+    highlight: ClickHighlight,
+    mouse: MouseTracker,
+}
+
+// A ring in the output's pixels.
+pub struct Ring {
+    x: f32,
+    y: f32,
+    radius: f32,
+    opacity: f32,
+}
+
+impl ClickOverlay {
+    pub fn new(highlight: ClickHighlight, mouse: MouseTracker) -> Self {
+        ClickOverlay { highlight, mouse }
+    }
+
+    // Draws the rings over the output, which send_frame_buffer takes bottom to top.
+    pub fn draw(
+        &self,
+        output: &mut [u8],
+        (width, height): (usize, usize),
+        rings: Vec<Ring>,
+        bottom_up: bool,
+    ) {
+        let [red, green, blue] = self.highlight.color;
+        let row = |y: usize| match bottom_up {
+            true => height - 1 - y,
+            false => y,
+        };
+
+        for ring in rings {
+            // The ring's stroke, anti-aliased over a pixel on either side.
+            let stroke = (ring.radius / 6.0).max(2.0);
+            let reach = ring.radius + stroke;
+            let left = (ring.x - reach).max(0.0) as usize;
+            let right = ((ring.x + reach).ceil().max(0.0) as usize).min(width);
+            let top = (ring.y - reach).max(0.0) as usize;
+            let bottom = ((ring.y + reach).ceil().max(0.0) as usize).min(height);
+            for y in top..bottom {
+                let start = row(y) * width * 4;
+                for x in left..right {
+                    let distance = (x as f32 + 0.5 - ring.x).hypot(y as f32 + 0.5 - ring.y);
+                    let coverage =
+                        (stroke / 2.0 - (distance - ring.radius).abs() + 0.5).clamp(0.0, 1.0);
+                    let alpha = coverage * ring.opacity;
+                    if alpha <= 0.0 {
+                        continue;
+                    }
+                    let pixel = &mut output[start + x * 4..start + x * 4 + 4];
+                    for (channel, color) in pixel.iter_mut().zip([blue, green, red]) {
+                        *channel = (*channel as f32 * (1.0 - alpha) + color as f32 * alpha) as u8;
+                    }
+                }
+            }
+        }
+    }
+
+    // The rings over a frame whose top left corner is at `origin` on the virtual screen. `map`
+    // tells where a point of the frame ends up in the output and how much larger things are there,
+    // so they are known before the frame is transformed.
+    pub fn rings(
+        &self,
+        origin: (i32, i32),
+        map: impl Fn(f32, f32) -> (f32, f32, f32),
+    ) -> Vec<Ring> {
+        let mouse = self.mouse.current();
+        let radius = self.highlight.radius as f32;
+        let ring = |(x, y): (i32, i32), radius: f32, opacity: f32| {
+            let (x, y, scale) = map((x - origin.0) as f32, (y - origin.1) as f32);
+            Ring {
+                x,
+                y,
+                radius: radius * scale,
+                opacity,
+            }
+        };
+
+        let mut rings = vec![];
+        if self.highlight.cursor {
+            if let Some(cursor) = mouse.cursor {
+                rings.push(ring(cursor, radius, CURSOR_OPACITY));
+            }
+        }
+        if let Some((position, at)) = mouse.clicked {
+            let duration = Duration::from_millis(self.highlight.duration_in_ms);
+            let age = at.elapsed().as_secs_f32() / duration.as_secs_f32();
+            // Grows by half while fading out.
+            if age < 1.0 {
+                rings.push(ring(position, radius * (1.0 + age / 2.0), 1.0 - age));
+            }
+        }
+
+        rings
+    }
+}
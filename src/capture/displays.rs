@@ -0,0 +1,431 @@
+use anyhow::Error;
+use serde::{Deserialize, Serialize};
+
+use crate::config::DisplaySelection;
+#[cfg(any(
+    all(windows, any(feature = "native", feature = "ffmpeg")),
+    feature = "x11",
+    feature = "synthetic"
+))]
+use crate::config::{CaptureRegion, DisplayLayout, OutputSize, ScaleMode};
+
+// The largest width and height of a region or a video, which is beyond what the encoders take.
+pub const MAX_SIZE: u32 = 16384;
+
+// A display a backend can capture. The position is in the pixels of the virtual screen, which
+// spans every display.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct DisplayInfo {
+    pub index: usize,
+    pub name: String,
+    // A friendlier name, e.g. the monitor's model.
+    pub description: Option<String>,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub primary: bool,
+}
+
+// The displays to capture, the primary one when nothing is selected.
+pub fn select(
+    displays: Vec<DisplayInfo>,
+    selection: Option<&DisplaySelection>,
+) -> Result<Vec<DisplayInfo>, anyhow::Error> {
+    let selected: Vec<DisplayInfo> = match selection {
+        None => displays
+            .iter()
+            .find(|display| display.primary)
+            .or(displays.first())
+            .cloned()
+            .into_iter()
+            .collect(),
+        Some(selection) if selection.is_all() => displays,
+        Some(DisplaySelection::Index(index)) => displays
+            .into_iter()
+            .filter(|display| display.index == *index)
+            .collect(),
+        Some(DisplaySelection::Name(name)) => displays
+            .into_iter()
+            .filter(|display| {
+                display.name == *name || display.description.as_deref() == Some(name.as_str())
+            })
+            .take(1)
+            .collect(),
+    };
+
+    match selected.is_empty() {
+        true => Err(Error::msg(match selection {
+            Some(DisplaySelection::Index(index)) => format!("There is no display {index}"),
+            Some(DisplaySelection::Name(name)) => format!("There is no display named {name}"),
+            None => "There is no display to capture".to_string(),
+        })),
+        false => Ok(selected),
+    }
+}
+
+// A rectangle of the virtual screen that is recorded into a video of its own, the video's name is
+// the session's video with the suffix appended.
+#[cfg(any(
+    all(windows, any(feature = "native", feature = "ffmpeg")),
+    feature = "x11",
+    feature = "synthetic"
+))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Region {
+    pub suffix: String,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[cfg(any(
+    all(windows, any(feature = "native", feature = "ffmpeg")),
+    feature = "x11",
+    feature = "synthetic"
+))]
+impl Region {
+    // The part of the region to record. Its size is rounded down to even, which the encoders need.
+    pub fn crop(&self, crop: Option<&CaptureRegion>) -> Result<Region, anyhow::Error> {
+        let crop = match crop {
+            Some(crop) => *crop,
+            None => CaptureRegion {
+                x: 0,
+                y: 0,
+                width: self.width,
+                height: self.height,
+            },
+        };
+        let fits = |offset: u32, size: u32, within: u32| {
+            offset.checked_add(size).is_some_and(|end| end <= within)
+        };
+        if !fits(crop.x, crop.width, self.width) || !fits(crop.y, crop.height, self.height) {
+            return Err(Error::msg(format!(
+                "The region {}x{}+{}+{} does not fit in the captured {}x{}",
+                crop.width, crop.height, crop.x, crop.y, self.width, self.height
+            )));
+        }
+
+        Ok(Region {
+            suffix: self.suffix.clone(),
+            x: self.x + crop.x as i32,
+            y: self.y + crop.y as i32,
+            width: crop.width & !1,
+            height: crop.height & !1,
+        })
+    }
+}
+
+// The size of the video for a recorded area, even like the area itself.
+#[cfg(any(
+    all(windows, any(feature = "native", feature = "ffmpeg")),
+    feature = "x11",
+    feature = "synthetic"
+))]
+pub fn scaled_size(
+    width: u32,
+    height: u32,
+    output: Option<OutputSize>,
+    scale: ScaleMode,
+) -> (u32, u32) {
+    let Some(output) = output else {
+        return (width, height);
+    };
+    let (scaled_width, scaled_height) = match scale {
+        ScaleMode::Stretch => (output.width, output.height),
+        ScaleMode::Fit => {
+            let factor = f64::min(
+                output.width as f64 / width as f64,
+                output.height as f64 / height as f64,
+            );
+            (
+                (width as f64 * factor).round() as u32,
+                (height as f64 * factor).round() as u32,
+            )
+        }
+    };
+
+    (
+        (scaled_width & !1).clamp(2, MAX_SIZE),
+        (scaled_height & !1).clamp(2, MAX_SIZE),
+    )
+}
+
+// The videos recording the displays take, a single one unless the layout is `Separate`.
+#[cfg(any(
+    all(windows, any(feature = "native", feature = "ffmpeg")),
+    feature = "x11",
+    feature = "synthetic"
+))]
+pub fn regions(displays: &[DisplayInfo], layout: DisplayLayout) -> Vec<Region> {
+    match layout {
+        DisplayLayout::Separate => displays
+            .iter()
+            .enumerate()
+            .map(|(position, display)| Region {
+                suffix: match position {
+                    0 => String::new(),
+                    _ => format!("-display{}", display.index),
+                },
+                x: display.x,
+                y: display.y,
+                width: display.width,
+                height: display.height,
+            })
+            .collect(),
+        DisplayLayout::Composite => vec![bounds(displays)],
+    }
+}
+
+// The smallest rectangle around the displays.
+#[cfg(any(
+    all(windows, any(feature = "native", feature = "ffmpeg")),
+    feature = "x11",
+    feature = "synthetic"
+))]
+fn bounds(displays: &[DisplayInfo]) -> Region {
+    let left = displays.iter().map(|display| display.x).min().unwrap_or(0);
+    let top = displays.iter().map(|display| display.y).min().unwrap_or(0);
+    let right = displays
+        .iter()
+        .map(|display| display.x + display.width as i32)
+        .max()
+        .unwrap_or(0);
+    let bottom = displays
+        .iter()
+        .map(|display| display.y + display.height as i32)
+        .max()
+        .unwrap_or(0);
+
+    Region {
+        suffix: String::new(),
+        x: left,
+        y: top,
+        width: (right - left) as u32,
+        height: (bottom - top) as u32,
+    }
+}
+
+// The monitors as windows-capture sees them, in its enumeration order.
+#[cfg(all(windows, any(feature = "native", feature = "ffmpeg")))]
+pub fn monitors() -> Result<Vec<(DisplayInfo, windows_capture::monitor::Monitor)>, anyhow::Error> {
+    use windows::{
+        core::HSTRING,
+        Win32::Graphics::Gdi::{EnumDisplaySettingsW, DEVMODEW, ENUM_CURRENT_SETTINGS},
+    };
+    use windows_capture::monitor::Monitor;
+
+    let mut monitors = vec![];
+    for (index, monitor) in Monitor::enumerate()?.into_iter().enumerate() {
+        let name = monitor.device_name()?;
+        // The position is not exposed by windows-capture, it reads the size the same way.
+        let mut device_mode = DEVMODEW {
+            dmSize: std::mem::size_of::<DEVMODEW>() as u16,
+            ..DEVMODEW::default()
+        };
+        let found = unsafe {
+            EnumDisplaySettingsW(
+                &HSTRING::from(&name),
+                ENUM_CURRENT_SETTINGS,
+                &mut device_mode,
+            )
+        };
+        if !found.as_bool() {
+            return Err(Error::msg(format!("Could not read the settings of {name}")));
+        }
+        let position = unsafe { device_mode.Anonymous1.Anonymous2.dmPosition };
+
+        let display = DisplayInfo {
+            index,
+            description: monitor.name().ok(),
+            name,
+            x: position.x,
+            y: position.y,
+            width: device_mode.dmPelsWidth,
+            height: device_mode.dmPelsHeight,
+            // The primary display is the origin of the virtual screen.
+            primary: position.x == 0 && position.y == 0,
+        };
+        monitors.push((display, monitor));
+    }
+
+    Ok(monitors)
+}
+
+// Most of them are about the regions, which only the capture backends record.
+#[cfg(all(
+    test,
+    any(
+        all(windows, any(feature = "native", feature = "ffmpeg")),
+        feature = "x11",
+        feature = "synthetic"
+    )
+))]
+mod tests {
+    use super::*;
+
+    fn display(index: usize, name: &str, x: i32, primary: bool) -> DisplayInfo {
+        DisplayInfo {
+            index,
+            name: name.to_string(),
+            description: Some(format!("Monitor {index}")),
+            x,
+            y: 0,
+            width: 1920,
+            height: 1080,
+            primary,
+        }
+    }
+
+    fn displays() -> Vec<DisplayInfo> {
+        vec![
+            display(0, "DP-1", -1920, false),
+            display(1, "DP-2", 0, true),
+        ]
+    }
+
+    fn names(selected: Vec<DisplayInfo>) -> Vec<String> {
+        selected.into_iter().map(|display| display.name).collect()
+    }
+
+    fn screen() -> Region {
+        Region {
+            suffix: "-display1".to_string(),
+            x: 1920,
+            y: 0,
+            width: 2560,
+            height: 1440,
+        }
+    }
+
+    #[test]
+    fn selects_the_primary_display_by_default() {
+        assert_eq!(names(select(displays(), None).unwrap()), ["DP-2"]);
+    }
+
+    #[test]
+    fn selects_by_index_name_or_description() {
+        let by_index = DisplaySelection::Index(0);
+        assert_eq!(
+            names(select(displays(), Some(&by_index)).unwrap()),
+            ["DP-1"]
+        );
+        let by_name = DisplaySelection::Name("DP-2".to_string());
+        assert_eq!(names(select(displays(), Some(&by_name)).unwrap()), ["DP-2"]);
+        let by_description = DisplaySelection::Name("Monitor 0".to_string());
+        assert_eq!(
+            names(select(displays(), Some(&by_description)).unwrap()),
+            ["DP-1"]
+        );
+        let all = DisplaySelection::Name("all".to_string());
+        assert_eq!(
+            names(select(displays(), Some(&all)).unwrap()),
+            ["DP-1", "DP-2"]
+        );
+    }
+
+    #[test]
+    fn fails_without_a_matching_display() {
+        assert!(select(displays(), Some(&DisplaySelection::Index(2))).is_err());
+        let missing = DisplaySelection::Name("HDMI-1".to_string());
+        assert!(select(displays(), Some(&missing)).is_err());
+        assert!(select(vec![], None).is_err());
+    }
+
+    #[test]
+    fn composes_the_bounds_of_the_displays() {
+        let composite = regions(&displays(), DisplayLayout::Composite);
+        assert_eq!(
+            composite,
+            [Region {
+                suffix: String::new(),
+                x: -1920,
+                y: 0,
+                width: 3840,
+                height: 1080,
+            }]
+        );
+        let separate = regions(&displays(), DisplayLayout::Separate);
+        let suffixes: Vec<&str> = separate
+            .iter()
+            .map(|region| region.suffix.as_str())
+            .collect();
+        assert_eq!(suffixes, ["", "-display1"]);
+    }
+
+    #[test]
+    fn crops_within_the_region() {
+        let crop = CaptureRegion {
+            x: 100,
+            y: 50,
+            width: 801,
+            height: 601,
+        };
+        assert_eq!(
+            screen().crop(Some(&crop)).unwrap(),
+            Region {
+                suffix: "-display1".to_string(),
+                x: 2020,
+                y: 50,
+                width: 800,
+                height: 600,
+            }
+        );
+        assert_eq!(screen().crop(None).unwrap(), screen());
+    }
+
+    #[test]
+    fn rejects_a_crop_beyond_the_region() {
+        let beyond = CaptureRegion {
+            x: 2000,
+            y: 0,
+            width: 561,
+            height: 100,
+        };
+        assert!(screen().crop(Some(&beyond)).is_err());
+        let overflowing = CaptureRegion {
+            x: u32::MAX,
+            y: 0,
+            width: 2,
+            height: 2,
+        };
+        assert!(screen().crop(Some(&overflowing)).is_err());
+    }
+
+    #[test]
+    fn scales_to_the_output_size() {
+        let output = Some(OutputSize {
+            width: 1280,
+            height: 1280,
+        });
+        assert_eq!(scaled_size(2560, 1440, None, ScaleMode::Fit), (2560, 1440));
+        assert_eq!(scaled_size(2560, 1440, output, ScaleMode::Fit), (1280, 720));
+        assert_eq!(
+            scaled_size(2560, 1440, output, ScaleMode::Stretch),
+            (1280, 1280)
+        );
+        // Even and at least 2x2, however far it is scaled down.
+        let tiny = Some(OutputSize {
+            width: 3,
+            height: 3,
+        });
+        assert_eq!(scaled_size(2560, 1440, tiny, ScaleMode::Fit), (2, 2));
+    }
+
+    #[test]
+    fn bounds_the_scaled_size() {
+        let huge = Some(OutputSize {
+            width: u32::MAX,
+            height: u32::MAX,
+        });
+        assert_eq!(
+            scaled_size(2, 2, huge, ScaleMode::Fit),
+            (MAX_SIZE, MAX_SIZE)
+        );
+        assert_eq!(
+            scaled_size(2, 2, huge, ScaleMode::Stretch),
+            (MAX_SIZE, MAX_SIZE)
+        );
+    }
+}
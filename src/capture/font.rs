@@ -0,0 +1,302 @@
+// DejaVu Sans Mono Bold at 12 pixels, rendered without anti-aliasing for the watermark. A glyph
+// is a byte per row, with the leftmost pixel in the high bit.
+
+pub const WIDTH: usize = 8;
+pub const HEIGHT: usize = 13;
+
+// The printable ASCII characters, from ' ' to '~'.
+const GLYPHS: [[u8; HEIGHT]; 95] = [
+    [
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ], // ' '
+    [
+        0x00, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x00, 0x18, 0x18, 0x00, 0x00, 0x00,
+    ], // '!'
+    [
+        0x00, 0x66, 0x66, 0x66, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ], // '"'
+    [
+        0x00, 0x00, 0x14, 0x34, 0x7e, 0x28, 0x28, 0xfc, 0x58, 0x50, 0x00, 0x00, 0x00,
+    ], // '#'
+    [
+        0x00, 0x10, 0x7c, 0xd4, 0xd0, 0x7c, 0x16, 0x16, 0xd6, 0x7c, 0x10, 0x10, 0x00,
+    ], // '$'
+    [
+        0x00, 0x60, 0x90, 0x90, 0x66, 0x18, 0xec, 0x12, 0x12, 0x0c, 0x00, 0x00, 0x00,
+    ], // '%'
+    [
+        0x00, 0x38, 0x30, 0x30, 0x10, 0x3a, 0x6a, 0x6e, 0x6c, 0x3e, 0x00, 0x00, 0x00,
+    ], // '&'
+    [
+        0x00, 0x18, 0x18, 0x18, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ], // '\''
+    [
+        0x08, 0x18, 0x10, 0x30, 0x30, 0x30, 0x30, 0x30, 0x10, 0x18, 0x08, 0x00, 0x00,
+    ], // '('
+    [
+        0x20, 0x30, 0x10, 0x18, 0x18, 0x18, 0x18, 0x18, 0x10, 0x30, 0x20, 0x00, 0x00,
+    ], // ')'
+    [
+        0x00, 0x10, 0x54, 0x38, 0x38, 0x54, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ], // '*'
+    [
+        0x00, 0x00, 0x00, 0x10, 0x10, 0x10, 0xfe, 0x10, 0x10, 0x10, 0x00, 0x00, 0x00,
+    ], // '+'
+    [
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x18, 0x18, 0x10, 0x20, 0x00,
+    ], // ','
+    [
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x3c, 0x3c, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ], // '-'
+    [
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x18, 0x18, 0x00, 0x00, 0x00,
+    ], // '.'
+    [
+        0x00, 0x02, 0x04, 0x04, 0x08, 0x08, 0x10, 0x10, 0x20, 0x20, 0x40, 0x00, 0x00,
+    ], // '/'
+    [
+        0x00, 0x3c, 0x24, 0x66, 0x66, 0x6e, 0x66, 0x66, 0x24, 0x3c, 0x00, 0x00, 0x00,
+    ], // '0'
+    [
+        0x00, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x7e, 0x00, 0x00, 0x00,
+    ], // '1'
+    [
+        0x00, 0x3c, 0x46, 0x06, 0x06, 0x0c, 0x18, 0x30, 0x60, 0x7e, 0x00, 0x00, 0x00,
+    ], // '2'
+    [
+        0x00, 0x3c, 0x46, 0x06, 0x06, 0x18, 0x06, 0x06, 0x46, 0x3c, 0x00, 0x00, 0x00,
+    ], // '3'
+    [
+        0x00, 0x0c, 0x1c, 0x1c, 0x2c, 0x6c, 0x4c, 0x7e, 0x0c, 0x0c, 0x00, 0x00, 0x00,
+    ], // '4'
+    [
+        0x00, 0x7c, 0x60, 0x60, 0x7c, 0x0e, 0x06, 0x06, 0x46, 0x3c, 0x00, 0x00, 0x00,
+    ], // '5'
+    [
+        0x00, 0x3c, 0x30, 0x60, 0x7c, 0x66, 0x66, 0x66, 0x66, 0x3c, 0x00, 0x00, 0x00,
+    ], // '6'
+    [
+        0x00, 0x7e, 0x06, 0x0e, 0x0c, 0x0c, 0x18, 0x18, 0x18, 0x30, 0x00, 0x00, 0x00,
+    ], // '7'
+    [
+        0x00, 0x3c, 0x66, 0x66, 0x66, 0x18, 0x66, 0x66, 0x66, 0x3c, 0x00, 0x00, 0x00,
+    ], // '8'
+    [
+        0x00, 0x3c, 0x66, 0x66, 0x66, 0x66, 0x3e, 0x06, 0x0c, 0x3c, 0x00, 0x00, 0x00,
+    ], // '9'
+    [
+        0x00, 0x00, 0x00, 0x00, 0x18, 0x18, 0x00, 0x00, 0x18, 0x18, 0x00, 0x00, 0x00,
+    ], // ':'
+    [
+        0x00, 0x00, 0x00, 0x00, 0x18, 0x18, 0x00, 0x00, 0x18, 0x18, 0x10, 0x20, 0x00,
+    ], // ';'
+    [
+        0x00, 0x00, 0x00, 0x02, 0x1e, 0x70, 0x70, 0x1e, 0x02, 0x00, 0x00, 0x00, 0x00,
+    ], // '<'
+    [
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x7e, 0x00, 0x7e, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ], // '='
+    [
+        0x00, 0x00, 0x00, 0x40, 0x78, 0x0e, 0x0e, 0x78, 0x40, 0x00, 0x00, 0x00, 0x00,
+    ], // '>'
+    [
+        0x00, 0x1c, 0x26, 0x06, 0x0c, 0x18, 0x18, 0x00, 0x18, 0x18, 0x00, 0x00, 0x00,
+    ], // '?'
+    [
+        0x00, 0x00, 0x3c, 0x42, 0xde, 0xa2, 0xa2, 0xa2, 0xa2, 0x5e, 0x62, 0x3e, 0x00,
+    ], // '@'
+    [
+        0x00, 0x18, 0x18, 0x18, 0x3c, 0x3c, 0x24, 0x3c, 0x66, 0x66, 0x00, 0x00, 0x00,
+    ], // 'A'
+    [
+        0x00, 0x7c, 0x66, 0x66, 0x66, 0x78, 0x66, 0x66, 0x66, 0x7c, 0x00, 0x00, 0x00,
+    ], // 'B'
+    [
+        0x00, 0x1c, 0x32, 0x60, 0x60, 0x60, 0x60, 0x60, 0x32, 0x1c, 0x00, 0x00, 0x00,
+    ], // 'C'
+    [
+        0x00, 0x78, 0x6c, 0x66, 0x66, 0x66, 0x66, 0x66, 0x6c, 0x78, 0x00, 0x00, 0x00,
+    ], // 'D'
+    [
+        0x00, 0x7e, 0x60, 0x60, 0x60, 0x7c, 0x60, 0x60, 0x60, 0x7e, 0x00, 0x00, 0x00,
+    ], // 'E'
+    [
+        0x00, 0x7e, 0x60, 0x60, 0x60, 0x7c, 0x60, 0x60, 0x60, 0x60, 0x00, 0x00, 0x00,
+    ], // 'F'
+    [
+        0x00, 0x1c, 0x32, 0x60, 0x60, 0x60, 0x6e, 0x66, 0x36, 0x1e, 0x00, 0x00, 0x00,
+    ], // 'G'
+    [
+        0x00, 0x66, 0x66, 0x66, 0x66, 0x7e, 0x66, 0x66, 0x66, 0x66, 0x00, 0x00, 0x00,
+    ], // 'H'
+    [
+        0x00, 0x7e, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x7e, 0x00, 0x00, 0x00,
+    ], // 'I'
+    [
+        0x00, 0x1e, 0x06, 0x06, 0x06, 0x06, 0x06, 0x06, 0x46, 0x3c, 0x00, 0x00, 0x00,
+    ], // 'J'
+    [
+        0x00, 0x66, 0x66, 0x6c, 0x78, 0x78, 0x6c, 0x64, 0x66, 0x63, 0x00, 0x00, 0x00,
+    ], // 'K'
+    [
+        0x00, 0x60, 0x60, 0x60, 0x60, 0x60, 0x60, 0x60, 0x60, 0x7e, 0x00, 0x00, 0x00,
+    ], // 'L'
+    [
+        0x00, 0x42, 0x66, 0x7e, 0x7e, 0x7e, 0x66, 0x66, 0x66, 0x66, 0x00, 0x00, 0x00,
+    ], // 'M'
+    [
+        0x00, 0x66, 0x76, 0x76, 0x76, 0x7e, 0x6e, 0x6e, 0x6e, 0x66, 0x00, 0x00, 0x00,
+    ], // 'N'
+    [
+        0x00, 0x3c, 0x24, 0x66, 0x66, 0x66, 0x66, 0x66, 0x24, 0x3c, 0x00, 0x00, 0x00,
+    ], // 'O'
+    [
+        0x00, 0x7c, 0x66, 0x66, 0x66, 0x7c, 0x60, 0x60, 0x60, 0x60, 0x00, 0x00, 0x00,
+    ], // 'P'
+    [
+        0x00, 0x3c, 0x24, 0x66, 0x66, 0x66, 0x66, 0x66, 0x24, 0x3c, 0x04, 0x00, 0x00,
+    ], // 'Q'
+    [
+        0x00, 0x7c, 0x66, 0x66, 0x66, 0x78, 0x6c, 0x66, 0x66, 0x63, 0x00, 0x00, 0x00,
+    ], // 'R'
+    [
+        0x00, 0x3c, 0x62, 0x60, 0x70, 0x3c, 0x0e, 0x06, 0x46, 0x3c, 0x00, 0x00, 0x00,
+    ], // 'S'
+    [
+        0x00, 0x7e, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x00, 0x00, 0x00,
+    ], // 'T'
+    [
+        0x00, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x3c, 0x00, 0x00, 0x00,
+    ], // 'U'
+    [
+        0x00, 0x66, 0x66, 0x24, 0x24, 0x3c, 0x3c, 0x3c, 0x18, 0x18, 0x00, 0x00, 0x00,
+    ], // 'V'
+    [
+        0x00, 0xc6, 0xc6, 0xd6, 0xd6, 0x6e, 0x6c, 0x6c, 0x6c, 0x6c, 0x00, 0x00, 0x00,
+    ], // 'W'
+    [
+        0x00, 0x66, 0x24, 0x3c, 0x18, 0x18, 0x18, 0x3c, 0x24, 0x66, 0x00, 0x00, 0x00,
+    ], // 'X'
+    [
+        0x00, 0xc3, 0x66, 0x66, 0x3c, 0x3c, 0x18, 0x18, 0x18, 0x18, 0x00, 0x00, 0x00,
+    ], // 'Y'
+    [
+        0x00, 0x7e, 0x06, 0x0c, 0x0c, 0x18, 0x30, 0x30, 0x60, 0x7e, 0x00, 0x00, 0x00,
+    ], // 'Z'
+    [
+        0x38, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x38, 0x00, 0x00,
+    ], // '['
+    [
+        0x00, 0x60, 0x20, 0x20, 0x10, 0x10, 0x08, 0x08, 0x04, 0x04, 0x06, 0x00, 0x00,
+    ], // '\\'
+    [
+        0x38, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x38, 0x00, 0x00,
+    ], // ']'
+    [
+        0x00, 0x38, 0x6c, 0xc6, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ], // '^'
+    [
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xfe,
+    ], // '_'
+    [
+        0x60, 0x30, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ], // '`'
+    [
+        0x00, 0x00, 0x00, 0x1c, 0x26, 0x06, 0x3e, 0x66, 0x66, 0x3e, 0x00, 0x00, 0x00,
+    ], // 'a'
+    [
+        0x60, 0x60, 0x60, 0x7c, 0x66, 0x66, 0x66, 0x66, 0x66, 0x7c, 0x00, 0x00, 0x00,
+    ], // 'b'
+    [
+        0x00, 0x00, 0x00, 0x1c, 0x32, 0x60, 0x60, 0x60, 0x32, 0x1c, 0x00, 0x00, 0x00,
+    ], // 'c'
+    [
+        0x06, 0x06, 0x06, 0x3e, 0x66, 0x66, 0x66, 0x66, 0x66, 0x3e, 0x00, 0x00, 0x00,
+    ], // 'd'
+    [
+        0x00, 0x00, 0x00, 0x3c, 0x66, 0x66, 0x7e, 0x60, 0x62, 0x3c, 0x00, 0x00, 0x00,
+    ], // 'e'
+    [
+        0x0e, 0x18, 0x18, 0x7e, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x00, 0x00, 0x00,
+    ], // 'f'
+    [
+        0x00, 0x00, 0x00, 0x3e, 0x66, 0x66, 0x66, 0x66, 0x66, 0x3e, 0x06, 0x06, 0x3c,
+    ], // 'g'
+    [
+        0x60, 0x60, 0x60, 0x7c, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x00, 0x00, 0x00,
+    ], // 'h'
+    [
+        0x18, 0x18, 0x00, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0x7e, 0x00, 0x00, 0x00,
+    ], // 'i'
+    [
+        0x18, 0x18, 0x00, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x70,
+    ], // 'j'
+    [
+        0x60, 0x60, 0x60, 0x64, 0x6c, 0x78, 0x78, 0x6c, 0x6c, 0x66, 0x00, 0x00, 0x00,
+    ], // 'k'
+    [
+        0xf0, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x1c, 0x00, 0x00, 0x00,
+    ], // 'l'
+    [
+        0x00, 0x00, 0x00, 0x7e, 0x6a, 0x6a, 0x6a, 0x6a, 0x6a, 0x6a, 0x00, 0x00, 0x00,
+    ], // 'm'
+    [
+        0x00, 0x00, 0x00, 0x7c, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x00, 0x00, 0x00,
+    ], // 'n'
+    [
+        0x00, 0x00, 0x00, 0x3c, 0x66, 0x66, 0x66, 0x66, 0x66, 0x3c, 0x00, 0x00, 0x00,
+    ], // 'o'
+    [
+        0x00, 0x00, 0x00, 0x7c, 0x66, 0x66, 0x66, 0x66, 0x66, 0x7c, 0x60, 0x60, 0x60,
+    ], // 'p'
+    [
+        0x00, 0x00, 0x00, 0x3e, 0x66, 0x66, 0x66, 0x66, 0x66, 0x3e, 0x06, 0x06, 0x06,
+    ], // 'q'
+    [
+        0x00, 0x00, 0x00, 0x3e, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x00, 0x00, 0x00,
+    ], // 'r'
+    [
+        0x00, 0x00, 0x00, 0x3c, 0x62, 0x70, 0x3c, 0x06, 0x46, 0x3c, 0x00, 0x00, 0x00,
+    ], // 's'
+    [
+        0x00, 0x30, 0x30, 0xfc, 0x30, 0x30, 0x30, 0x30, 0x30, 0x1c, 0x00, 0x00, 0x00,
+    ], // 't'
+    [
+        0x00, 0x00, 0x00, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x3e, 0x00, 0x00, 0x00,
+    ], // 'u'
+    [
+        0x00, 0x00, 0x00, 0x66, 0x66, 0x24, 0x3c, 0x3c, 0x18, 0x18, 0x00, 0x00, 0x00,
+    ], // 'v'
+    [
+        0x00, 0x00, 0x00, 0xc6, 0xc6, 0xd6, 0x6c, 0x6c, 0x6c, 0x6c, 0x00, 0x00, 0x00,
+    ], // 'w'
+    [
+        0x00, 0x00, 0x00, 0x66, 0x3c, 0x3c, 0x18, 0x3c, 0x3c, 0x66, 0x00, 0x00, 0x00,
+    ], // 'x'
+    [
+        0x00, 0x00, 0x00, 0x66, 0x66, 0x24, 0x3c, 0x3c, 0x18, 0x18, 0x18, 0x10, 0x70,
+    ], // 'y'
+    [
+        0x00, 0x00, 0x00, 0x7e, 0x06, 0x0c, 0x18, 0x30, 0x60, 0x7e, 0x00, 0x00, 0x00,
+    ], // 'z'
+    [
+        0x1e, 0x18, 0x18, 0x18, 0x18, 0x60, 0x18, 0x18, 0x18, 0x18, 0x1e, 0x00, 0x00,
+    ], // '{'
+    [
+        0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x00,
+    ], // '|'
+    [
+        0x78, 0x18, 0x18, 0x18, 0x18, 0x06, 0x18, 0x18, 0x18, 0x18, 0x78, 0x00, 0x00,
+    ], // '}'
+    [
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x70, 0x0e, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ], // '~'
+];
+
+// Characters without a glyph are drawn as '?'.
+pub fn glyph(character: char) -> &'static [u8; HEIGHT] {
+    match character {
+        ' '..='~' => &GLYPHS[character as usize - ' ' as usize],
+        _ => &GLYPHS['?' as usize - ' ' as usize],
+    }
+}
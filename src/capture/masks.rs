@@ -0,0 +1,270 @@
+use log::warn;
+use std::{
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+#[cfg(all(windows, any(feature = "native", feature = "ffmpeg")))]
+use super::window::{screen_rect, WindowMatcher};
+use crate::{
+    config::{MaskArea, MaskStyle, PrivacyMask},
+    session::Session,
+};
+
+// Looking for the masked windows lists every window, so it is not done on every frame. A window
+// that starts to match is only masked once it is found, where the windows found are is looked up
+// whenever the masks are asked for.
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+// Pixelated masks are made of blocks this many pixels wide.
+pub const BLOCK_SIZE: usize = 16;
+
+// A masked rectangle of the virtual screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MaskRect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub style: MaskStyle,
+}
+
+impl MaskRect {
+    // The left, top, right and bottom of the rectangle in a frame that starts at `origin`, cut
+    // where the frame starts. Where the frame ends is up to the caller.
+    pub fn within(&self, origin: (i32, i32)) -> (usize, usize, usize, usize) {
+        let left = self.x as i64 - origin.0 as i64;
+        let top = self.y as i64 - origin.1 as i64;
+        let right = left + self.width as i64;
+        let bottom = top + self.height as i64;
+        let clamp = |value: i64| value.max(0) as usize;
+
+        (clamp(left), clamp(top), clamp(right), clamp(bottom))
+    }
+}
+
+// Covers the whole virtual screen, wherever it starts.
+const EVERYTHING: MaskRect = MaskRect {
+    x: i32::MIN,
+    y: i32::MIN,
+    width: u32::MAX,
+    height: u32::MAX,
+    style: MaskStyle::Blackout,
+};
+
+// The windows a window mask matched, with where each of them was last time.
+#[derive(Debug, Clone)]
+struct MaskedWindows {
+    mask: PrivacyMask,
+    windows: Vec<(FoundWindow, MaskRect)>,
+}
+
+// Where the session's masks are. The rectangles come straight from the session, the windows open
+// and close so a thread looks for them again and again.
+#[derive(Debug, Clone)]
+pub struct MaskTracker {
+    session: Arc<Session>,
+    windows: Arc<Mutex<Vec<MaskedWindows>>>,
+}
+
+impl MaskTracker {
+    // Follows the windows for as long as the session captures.
+    pub fn start(session: Arc<Session>) -> Self {
+        let tracker = MaskTracker {
+            session,
+            windows: Arc::new(Mutex::new(vec![])),
+        };
+        tracker.find_windows();
+
+        let polling = tracker.clone();
+        thread::spawn(move || {
+            while polling.session.is_capturing() {
+                thread::sleep(POLL_INTERVAL);
+                polling.find_windows();
+            }
+        });
+
+        tracker
+    }
+
+    // The masks to hide in the next frame. The windows found are where they are now, e.g. while
+    // they are dragged.
+    pub fn current(&self) -> Vec<MaskRect> {
+        if self.session.is_video_hidden() {
+            return vec![EVERYTHING];
+        }
+        let masks = self.session.privacy_masks();
+        // A window mask that was just added is looked up right away, rather than at the next poll.
+        let unknown = |windows: &[MaskedWindows]| {
+            masks.iter().any(|mask| {
+                matches!(mask.area, MaskArea::Window { .. })
+                    && !windows.iter().any(|known| known.mask == *mask)
+            })
+        };
+        if unknown(&self.windows.lock().unwrap()) {
+            self.find_windows();
+        }
+
+        let mut windows = self.windows.lock().unwrap();
+        for (window, rect) in windows
+            .iter_mut()
+            .flat_map(|known| known.windows.iter_mut())
+        {
+            // A window that just closed stays hidden where it was until the next poll.
+            if let Some(moved) = window_rect(window, rect.style) {
+                *rect = moved;
+            }
+        }
+        masks
+            .iter()
+            .flat_map(|mask| match &mask.area {
+                &MaskArea::Rect {
+                    x,
+                    y,
+                    width,
+                    height,
+                } => vec![MaskRect {
+                    x,
+                    y,
+                    width,
+                    height,
+                    style: mask.style,
+                }],
+                MaskArea::Window { .. } => windows
+                    .iter()
+                    .filter(|known| known.mask == *mask)
+                    .flat_map(|known| known.windows.iter().map(|(_, rect)| *rect))
+                    .collect(),
+            })
+            .collect()
+    }
+
+    fn find_windows(&self) {
+        let previous = self.windows.lock().unwrap().clone();
+        let found = self
+            .session
+            .privacy_masks()
+            .into_iter()
+            .filter(|mask| matches!(mask.area, MaskArea::Window { .. }))
+            .map(|mask| {
+                let windows = matching_windows(&mask).unwrap_or_else(|err| {
+                    // Keeps hiding where the windows were, rather than nothing.
+                    warn!("Could not look for the masked windows, {err}");
+                    previous
+                        .iter()
+                        .filter(|known| known.mask == mask)
+                        .flat_map(|known| known.windows.clone())
+                        .collect()
+                });
+                MaskedWindows { mask, windows }
+            })
+            .collect();
+        *self.windows.lock().unwrap() = found;
+    }
+}
+
+#[cfg(all(windows, any(feature = "native", feature = "ffmpeg")))]
+type FoundWindow = windows_capture::window::Window;
+
+// Only the backends on Windows can mask windows, see `Capabilities::window_masks`.
+#[cfg(not(all(windows, any(feature = "native", feature = "ffmpeg"))))]
+type FoundWindow = ();
+
+#[cfg(all(windows, any(feature = "native", feature = "ffmpeg")))]
+fn matching_windows(mask: &PrivacyMask) -> Result<Vec<(FoundWindow, MaskRect)>, anyhow::Error> {
+    let MaskArea::Window { title, process } = &mask.area else {
+        return Ok(vec![]);
+    };
+    let windows = WindowMatcher::new(title.as_deref(), process.as_deref())?.find_all()?;
+
+    Ok(windows
+        .into_iter()
+        .filter_map(|window| Some((window, window_rect(&window, mask.style)?)))
+        .collect())
+}
+
+#[cfg(all(windows, any(feature = "native", feature = "ffmpeg")))]
+fn window_rect(window: &FoundWindow, style: MaskStyle) -> Option<MaskRect> {
+    let rect = screen_rect(window).ok()?;
+
+    Some(MaskRect {
+        x: rect.left,
+        y: rect.top,
+        width: (rect.right - rect.left).max(0) as u32,
+        height: (rect.bottom - rect.top).max(0) as u32,
+        style,
+    })
+}
+
+#[cfg(not(all(windows, any(feature = "native", feature = "ffmpeg"))))]
+fn matching_windows(_mask: &PrivacyMask) -> Result<Vec<(FoundWindow, MaskRect)>, anyhow::Error> {
+    Ok(vec![])
+}
+
+#[cfg(not(all(windows, any(feature = "native", feature = "ffmpeg"))))]
+fn window_rect(_window: &FoundWindow, _style: MaskStyle) -> Option<MaskRect> {
+    None
+}
+
+// Hides the part of a BGRA frame given by its left, top, right and bottom. Frames going to
+// send_frame_buffer are bottom to top.
+#[cfg(any(all(windows, feature = "native"), feature = "synthetic"))]
+pub fn hide(
+    frame: &mut [u8],
+    width: usize,
+    (left, top, right, bottom): (usize, usize, usize, usize),
+    style: MaskStyle,
+    bottom_up: bool,
+) {
+    let height = frame.len() / (width * 4).max(1);
+    let (right, bottom) = (right.min(width), bottom.min(height));
+    if left >= right || top >= bottom {
+        return;
+    }
+    let row = |y: usize| match bottom_up {
+        true => height - 1 - y,
+        false => y,
+    };
+
+    match style {
+        MaskStyle::Blackout => {
+            for y in top..bottom {
+                let start = row(y) * width * 4;
+                for pixel in frame[start + left * 4..start + right * 4].chunks_exact_mut(4) {
+                    pixel[..3].fill(0);
+                }
+            }
+        }
+        MaskStyle::Pixelate => {
+            for block_top in (top..bottom).step_by(BLOCK_SIZE) {
+                let block_bottom = (block_top + BLOCK_SIZE).min(bottom);
+                for block_left in (left..right).step_by(BLOCK_SIZE) {
+                    let block_right = (block_left + BLOCK_SIZE).min(right);
+                    let rows = || {
+                        (block_top..block_bottom).map(|y| {
+                            let start = row(y) * width * 4;
+                            start + block_left * 4..start + block_right * 4
+                        })
+                    };
+
+                    let mut sums = [0u64; 3];
+                    for range in rows() {
+                        for pixel in frame[range].chunks_exact(4) {
+                            for (sum, channel) in sums.iter_mut().zip(pixel) {
+                                *sum += *channel as u64;
+                            }
+                        }
+                    }
+                    let count = ((block_right - block_left) * (block_bottom - block_top)) as u64;
+                    let average = sums.map(|sum| (sum / count) as u8);
+                    for range in rows() {
+                        for pixel in frame[range].chunks_exact_mut(4) {
+                            pixel[..3].copy_from_slice(&average);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
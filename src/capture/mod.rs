@@ -1,18 +1,174 @@
-use std::sync::{Arc, Mutex};
-
-use crate::{config::CaptureConfig, ffmpeg, native_capture};
-
-pub fn capture_screen(
-    recording: Arc<Mutex<bool>>,
-    recording_raw: Arc<Mutex<bool>>,
-    filename: String,
-    capture_config: CaptureConfig,
-) -> Result<(), anyhow::Error> {
-    if capture_config.ffmpeg {
-        ffmpeg::capture::ffmpeg_capture(recording, recording_raw, filename, capture_config)?;
-    } else {
-        native_capture::record_screen(recording, recording_raw, filename, capture_config)?;
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::Error;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+#[cfg(all(windows, feature = "native"))]
+use crate::native_capture;
+use crate::{
+    config::{CaptureConfig, Config},
+    session::Session,
+};
+#[cfg(any(all(windows, feature = "ffmpeg"), feature = "x11"))]
+use crate::ffmpeg;
+#[cfg(feature = "synthetic")]
+use crate::synthetic;
+use displays::DisplayInfo;
+
+#[cfg(any(all(windows, feature = "native"), feature = "synthetic"))]
+pub mod clicks;
+pub mod displays;
+#[cfg(any(all(windows, feature = "native"), feature = "synthetic"))]
+mod font;
+#[cfg(any(
+    all(windows, any(feature = "native", feature = "ffmpeg")),
+    feature = "x11",
+    feature = "synthetic"
+))]
+pub mod masks;
+#[cfg(any(
+    all(windows, any(feature = "native", feature = "ffmpeg")),
+    feature = "x11",
+    feature = "synthetic"
+))]
+pub mod mouse;
+#[cfg(any(all(windows, feature = "native"), feature = "synthetic"))]
+pub mod watermark;
+pub mod window;
+
+// What a backend can do, so the api can refuse what it can not.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy)]
+pub struct Capabilities {
+    pub pause: bool,
+    pub rotation: bool,
+    // Whether the backend sees the individual frames, see `CaptureStats`.
+    pub frame_stats: bool,
+    // Whether the backend can capture a single window, see `CaptureConfig::window`.
+    pub window: bool,
+    // Whether the backend can draw `CaptureConfig::click_highlight` into the video.
+    pub click_highlight: bool,
+    // Whether the backend can hide the windows among `CaptureConfig::privacy_masks`, every backend
+    // hides the rectangles.
+    pub window_masks: bool,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct CaptureStats {
+    pub running: bool,
+    // The session segment the backend is writing.
+    pub segment: usize,
+    pub frames_received: Option<u64>,
+    pub frames_encoded: Option<u64>,
+}
+
+// A source of video. The capture runs on threads of its own and follows the session state, so
+// `stop` and `pause` are called after the session changed and only have to act on it.
+pub trait CaptureBackend: Send + Sync {
+    fn capabilities(&self) -> Capabilities;
+
+    fn start(
+        &self,
+        session: Arc<Session>,
+        filename: String,
+        capture_config: CaptureConfig,
+    ) -> Result<(), anyhow::Error>;
+
+    fn stop(&self, _session: &Session) {}
+
+    fn pause(&self, _session: &Session, _paused: bool) -> Result<(), anyhow::Error> {
+        match self.capabilities().pause {
+            true => Ok(()),
+            false => Err(Error::msg("The capture backend can not pause")),
+        }
+    }
+
+    // The displays `CaptureConfig::display` may select.
+    fn displays(&self) -> Result<Vec<DisplayInfo>, anyhow::Error>;
+
+    fn stats(&self, session: &Session) -> CaptureStats {
+        let (frames_received, frames_encoded) = session.frames();
+        let frame_stats = self.capabilities().frame_stats;
+        CaptureStats {
+            running: session.is_video_running(),
+            segment: session.video_segment(),
+            frames_received: frame_stats.then_some(frames_received),
+            frames_encoded: frame_stats.then_some(frames_encoded),
+        }
+    }
+}
+
+// Builds a backend from the settings of its config section.
+type Factory = fn(Map<String, Value>) -> Result<Arc<dyn CaptureBackend>, anyhow::Error>;
+
+// Every kind of backend a config section may use.
+const KNOWN_KINDS: [&str; 4] = ["native", "ffmpeg", "x11", "synthetic"];
+
+// The kinds that are built for this platform and set of features.
+const KINDS: &[(&str, Factory)] = &[
+    #[cfg(all(windows, feature = "native"))]
+    ("native", native_capture::backend),
+    #[cfg(all(windows, feature = "ffmpeg"))]
+    ("ffmpeg", ffmpeg::capture::backend),
+    #[cfg(feature = "x11")]
+    ("x11", ffmpeg::x11::backend),
+    #[cfg(feature = "synthetic")]
+    ("synthetic", synthetic::backend),
+];
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct BackendInfo {
+    pub name: String,
+    pub kind: String,
+    pub capabilities: Capabilities,
+}
+
+// The configured backends, keyed by their name in config.json.
+pub struct Backends {
+    backends: HashMap<String, (String, Arc<dyn CaptureBackend>)>,
+}
+
+impl Backends {
+    pub fn from_config(config: &Config) -> Result<Self, anyhow::Error> {
+        let mut backends = HashMap::new();
+        for (name, section) in &config.backends {
+            if !KNOWN_KINDS.contains(&section.kind.as_str()) {
+                return Err(Error::msg(format!(
+                    "Backend {name} has an unknown kind {}",
+                    section.kind
+                )));
+            }
+            let Some((_, factory)) = KINDS.iter().find(|(kind, _)| *kind == section.kind) else {
+                warn!(
+                    "Backend {name} is not available, {} is not built for this platform",
+                    section.kind
+                );
+                continue;
+            };
+            let backend = factory(section.settings.clone())
+                .map_err(|err| Error::msg(format!("Backend {name} is misconfigured, {err}")))?;
+            backends.insert(name.clone(), (section.kind.clone(), backend));
+        }
+
+        Ok(Backends { backends })
     }
 
-    Ok(())
+    pub fn get(&self, name: &str) -> Option<Arc<dyn CaptureBackend>> {
+        self.backends.get(name).map(|(_, backend)| backend.clone())
+    }
+
+    pub fn list(&self) -> Vec<BackendInfo> {
+        let mut backends: Vec<BackendInfo> = self
+            .backends
+            .iter()
+            .map(|(name, (kind, backend))| BackendInfo {
+                name: name.clone(),
+                kind: kind.clone(),
+                capabilities: backend.capabilities(),
+            })
+            .collect();
+        backends.sort_by(|a, b| a.name.cmp(&b.name));
+        backends
+    }
 }
@@ -0,0 +1,229 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use crate::session::Session;
+
+// Where the mouse is, on the virtual screen.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Mouse {
+    pub cursor: Option<(i32, i32)>,
+    // Where a button was last pressed, and when.
+    pub clicked: Option<((i32, i32), Instant)>,
+}
+
+// Follows the mouse for as long as the session captures, from whatever the backend's platform has
+// to tell about it. Clones share the mouse, so every display of a capture shows the same clicks.
+#[derive(Debug, Clone)]
+pub struct MouseTracker {
+    mouse: Arc<Mutex<Mouse>>,
+}
+
+impl MouseTracker {
+    pub fn current(&self) -> Mouse {
+        *self.mouse.lock().unwrap()
+    }
+
+    // Polls the cursor and the buttons.
+    #[cfg(all(windows, any(feature = "native", feature = "ffmpeg")))]
+    pub fn start(session: Arc<Session>) -> Self {
+        use std::{thread, time::Duration};
+        use windows::Win32::{
+            Foundation::POINT,
+            UI::{
+                Input::KeyboardAndMouse::{GetAsyncKeyState, VK_LBUTTON, VK_RBUTTON},
+                WindowsAndMessaging::GetCursorPos,
+            },
+        };
+
+        // Short enough to notice a quick click.
+        const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+        let tracker = MouseTracker {
+            mouse: Arc::new(Mutex::new(Mouse::default())),
+        };
+        let polled = tracker.mouse.clone();
+        thread::spawn(move || {
+            let mut pressed = false;
+            while session.is_capturing() {
+                let mut point = POINT::default();
+                let cursor = unsafe { GetCursorPos(&mut point) }
+                    .is_ok()
+                    .then_some((point.x, point.y));
+                // The low bit is a press since the last call, which may have been shorter than the
+                // interval.
+                let down = [VK_LBUTTON, VK_RBUTTON]
+                    .iter()
+                    .any(|key| unsafe { GetAsyncKeyState(key.0 as i32) } as u16 & 0x8001 != 0);
+
+                let mut mouse = polled.lock().unwrap();
+                mouse.cursor = cursor;
+                if down && !pressed {
+                    mouse.clicked = cursor.map(|cursor| (cursor, Instant::now()));
+                }
+                pressed = down;
+                drop(mouse);
+
+                thread::sleep(POLL_INTERVAL);
+            }
+        });
+
+        tracker
+    }
+
+    // Listens for the presses on an X11 display with `xinput test-xi2`, which sees them whichever
+    // window gets them, and asks `xdotool` where they were. The cursor is only polled when
+    // `follow_cursor`, a process per poll is not cheap.
+    #[cfg(feature = "x11")]
+    pub fn x11(
+        session: Arc<Session>,
+        display: &str,
+        follow_cursor: bool,
+    ) -> Result<Self, anyhow::Error> {
+        use anyhow::Error;
+        use log::warn;
+        use std::{
+            io::{BufRead, BufReader},
+            process::{Command, Stdio},
+            thread,
+            time::Duration,
+        };
+
+        const CURSOR_INTERVAL: Duration = Duration::from_millis(100);
+
+        let locate = {
+            let display = display.to_string();
+            move || -> Option<(i32, i32)> {
+                let output = Command::new("xdotool")
+                    .args(["getmouselocation", "--shell"])
+                    .env("DISPLAY", &display)
+                    .output()
+                    .ok()?;
+                parse_mouse_location(&String::from_utf8_lossy(&output.stdout))
+            }
+        };
+        let mut child = Command::new("xinput")
+            .args(["test-xi2", "--root"])
+            .env("DISPLAY", display)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .or(Err(Error::msg("Could not run xinput to follow the clicks")))?;
+        let events = child
+            .stdout
+            .take()
+            .ok_or(Error::msg("xinput has no stdout"))?;
+
+        let tracker = MouseTracker {
+            mouse: Arc::new(Mutex::new(Mouse::default())),
+        };
+        let pressed = tracker.mouse.clone();
+        let located = locate.clone();
+        thread::spawn(move || {
+            // An event is a line naming it, followed by its fields. Raw presses are reported to
+            // the root window even when another one takes them.
+            let mut press = false;
+            for line in BufReader::new(events).lines().map_while(Result::ok) {
+                let line = line.trim();
+                if line.starts_with("EVENT type") {
+                    press = line.ends_with("(RawButtonPress)");
+                } else if press && line.starts_with("detail:") {
+                    press = false;
+                    // The left and the right button, the others scroll or are rarely clicked.
+                    if matches!(line.trim_start_matches("detail:").trim(), "1" | "3") {
+                        if let Some(cursor) = located() {
+                            let mut mouse = pressed.lock().unwrap();
+                            mouse.cursor = Some(cursor);
+                            mouse.clicked = Some((cursor, Instant::now()));
+                        }
+                    }
+                }
+            }
+        });
+
+        let polled = tracker.mouse.clone();
+        thread::spawn(move || {
+            while session.is_capturing() {
+                if follow_cursor {
+                    if let Some(cursor) = locate() {
+                        polled.lock().unwrap().cursor = Some(cursor);
+                    }
+                }
+                thread::sleep(CURSOR_INTERVAL);
+            }
+            // Ends the listening thread too, with the output.
+            if let Err(err) = child.kill() {
+                warn!("Could not stop xinput, {err}");
+            }
+            let _ = child.wait();
+        });
+
+        Ok(tracker)
+    }
+
+    // A made up mouse for the test pattern, circling around the middle of the screen and clicking
+    // once every `CLICK_INTERVAL`.
+    #[cfg(feature = "synthetic")]
+    pub fn simulated(session: Arc<Session>, (width, height): (u32, u32)) -> Self {
+        use std::{f32::consts::TAU, thread, time::Duration};
+
+        const POLL_INTERVAL: Duration = Duration::from_millis(10);
+        const CLICK_INTERVAL: Duration = Duration::from_secs(1);
+        // A lap takes this many clicks.
+        const CLICKS_PER_LAP: f32 = 4.0;
+
+        let tracker = MouseTracker {
+            mouse: Arc::new(Mutex::new(Mouse::default())),
+        };
+        let moved = tracker.mouse.clone();
+        thread::spawn(move || {
+            let start = Instant::now();
+            let mut clicks = 0;
+            while session.is_capturing() {
+                let elapsed = start.elapsed().as_secs_f32() / CLICK_INTERVAL.as_secs_f32();
+                let angle = elapsed / CLICKS_PER_LAP * TAU;
+                let cursor = (
+                    (width as f32 * (0.5 + angle.cos() / 4.0)) as i32,
+                    (height as f32 * (0.5 + angle.sin() / 4.0)) as i32,
+                );
+
+                let mut mouse = moved.lock().unwrap();
+                mouse.cursor = Some(cursor);
+                if elapsed as u32 > clicks {
+                    clicks = elapsed as u32;
+                    mouse.clicked = Some((cursor, Instant::now()));
+                }
+                drop(mouse);
+
+                thread::sleep(POLL_INTERVAL);
+            }
+        });
+
+        tracker
+    }
+}
+
+// `xdotool getmouselocation --shell` prints lines like `X=1023`, `Y=542`, `SCREEN=0`, `WINDOW=...`.
+#[cfg(feature = "x11")]
+fn parse_mouse_location(output: &str) -> Option<(i32, i32)> {
+    let value = |name: &str| {
+        output
+            .lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix('=')?.parse().ok())
+    };
+
+    Some((value("X")?, value("Y")?))
+}
+
+#[cfg(all(test, feature = "x11"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_the_mouse_location() {
+        let output = "X=1023\nY=542\nSCREEN=0\nWINDOW=4194311\n";
+        assert_eq!(parse_mouse_location(output), Some((1023, 542)));
+        assert_eq!(parse_mouse_location("X=1023\n"), None);
+    }
+}
@@ -0,0 +1,91 @@
+use chrono::Local;
+
+use super::font;
+use crate::config::{Corner, Watermark};
+
+// Renders a `Watermark` into BGRA frames, for the backends that see the pixels.
+#[derive(Debug, Clone)]
+pub struct WatermarkOverlay {
+    labels: Vec<String>,
+    corner: Corner,
+    scale: usize,
+}
+
+impl WatermarkOverlay {
+    pub fn new(watermark: &Watermark, session_id: &str) -> Self {
+        WatermarkOverlay {
+            labels: watermark.labels(session_id),
+            corner: watermark.corner,
+            scale: watermark.scale as usize,
+        }
+    }
+
+    // Draws into the area of the frame given by its left, top, width and height, on a dimmed
+    // background. Frames going to send_frame_buffer are bottom to top.
+    pub fn draw(
+        &self,
+        frame: &mut [u8],
+        width: usize,
+        (left, top, area_width, area_height): (usize, usize, usize, usize),
+        bottom_up: bool,
+    ) {
+        let time = Local::now().format("%Y-%m-%d %H:%M:%S%.3f").to_string();
+        let lines: Vec<&str> = std::iter::once(time.as_str())
+            .chain(self.labels.iter().map(String::as_str))
+            .collect();
+
+        let scale = self.scale;
+        let padding = 2 * scale;
+        let columns = lines
+            .iter()
+            .map(|line| line.chars().count())
+            .max()
+            .unwrap_or(0);
+        let box_width = (columns * font::WIDTH * scale + 2 * padding).min(area_width);
+        let box_height = (lines.len() * font::HEIGHT * scale + 2 * padding).min(area_height);
+        let box_left = match self.corner {
+            Corner::TopLeft | Corner::BottomLeft => left,
+            Corner::TopRight | Corner::BottomRight => left + area_width - box_width,
+        };
+        let box_top = match self.corner {
+            Corner::TopLeft | Corner::TopRight => top,
+            Corner::BottomLeft | Corner::BottomRight => top + area_height - box_height,
+        };
+
+        let height = frame.len() / (width * 4).max(1);
+        let offset = |x: usize, y: usize| {
+            let row = match bottom_up {
+                true => height - 1 - y,
+                false => y,
+            };
+            (row * width + x) * 4
+        };
+
+        let (right, bottom) = (box_left + box_width, box_top + box_height);
+        for y in box_top..bottom {
+            for x in box_left..right {
+                let offset = offset(x, y);
+                for channel in &mut frame[offset..offset + 3] {
+                    *channel /= 3;
+                }
+            }
+        }
+        for (line, text) in lines.iter().enumerate() {
+            for (column, character) in text.chars().enumerate() {
+                for (glyph_y, bits) in font::glyph(character).iter().enumerate() {
+                    let top = box_top + padding + (line * font::HEIGHT + glyph_y) * scale;
+                    for glyph_x in (0..font::WIDTH).filter(|x| bits & (0x80 >> x) != 0) {
+                        let left = box_left + padding + (column * font::WIDTH + glyph_x) * scale;
+                        // A square per pixel of the font, clipped to the box.
+                        for y in top..(top + scale).min(bottom) {
+                            for x in left..(left + scale).min(right) {
+                                let offset = offset(x, y);
+                                frame[offset..offset + 3].fill(255);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
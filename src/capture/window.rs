@@ -0,0 +1,89 @@
+use anyhow::Error;
+use regex::Regex;
+
+// Tells whether a window is the one a `WindowTarget`, a window `MaskArea` or a `WindowPattern`
+// asks for. Only the backends on Windows look for windows, the focus watch matches everywhere.
+#[derive(Debug, Clone)]
+pub struct WindowMatcher {
+    title: Option<Regex>,
+    process: Option<String>,
+}
+
+impl WindowMatcher {
+    pub fn new(title: Option<&str>, process: Option<&str>) -> Result<Self, anyhow::Error> {
+        if title.is_none() && process.is_none() {
+            return Err(Error::msg("window needs a title or a process to match"));
+        }
+        let title = title
+            .map(Regex::new)
+            .transpose()
+            .map_err(|err| Error::msg(format!("window title is not a valid regex, {err}")))?;
+
+        Ok(WindowMatcher {
+            title,
+            process: process.map(str::to_string),
+        })
+    }
+
+    pub fn matches(&self, title: &str, process: &str) -> bool {
+        self.title
+            .as_ref()
+            .is_none_or(|regex| regex.is_match(title))
+            && self
+                .process
+                .as_ref()
+                .is_none_or(|name| name.eq_ignore_ascii_case(process))
+    }
+
+    // The first open window that matches, with its title.
+    #[cfg(all(windows, feature = "native"))]
+    pub fn find(&self) -> Result<Option<(windows_capture::window::Window, String)>, anyhow::Error> {
+        for window in windows_capture::window::Window::enumerate()? {
+            // Windows of processes the recorder may not inspect have no process name.
+            let (Ok(title), Ok(process)) = (window.title(), window.process_name()) else {
+                continue;
+            };
+            if self.matches(&title, &process) {
+                return Ok(Some((window, title)));
+            }
+        }
+
+        Ok(None)
+    }
+
+    // Every open window that matches.
+    #[cfg(all(windows, any(feature = "native", feature = "ffmpeg")))]
+    pub fn find_all(&self) -> Result<Vec<windows_capture::window::Window>, anyhow::Error> {
+        Ok(windows_capture::window::Window::enumerate()?
+            .into_iter()
+            .filter(|window| match (window.title(), window.process_name()) {
+                (Ok(title), Ok(process)) => self.matches(&title, &process),
+                _ => false,
+            })
+            .collect())
+    }
+}
+
+// Where a window is on the screen. GetWindowRect also counts the invisible borders windows get to
+// be resized by, which are not on the screen.
+#[cfg(all(windows, any(feature = "native", feature = "ffmpeg")))]
+pub fn screen_rect(
+    window: &windows_capture::window::Window,
+) -> Result<windows::Win32::Foundation::RECT, anyhow::Error> {
+    use windows::Win32::{
+        Foundation::{HWND, RECT},
+        Graphics::Dwm::{DwmGetWindowAttribute, DWMWA_EXTENDED_FRAME_BOUNDS},
+    };
+
+    let mut rect = RECT::default();
+    unsafe {
+        DwmGetWindowAttribute(
+            HWND(window.as_raw_hwnd()),
+            DWMWA_EXTENDED_FRAME_BOUNDS,
+            &mut rect as *mut RECT as *mut std::ffi::c_void,
+            std::mem::size_of::<RECT>() as u32,
+        )?;
+    }
+
+    Ok(rect)
+}
@@ -1,20 +1,697 @@
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv4Addr},
+};
+
 use config_file::FromConfigFile;
-use serde::Deserialize;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::capture::window::WindowMatcher;
 
 #[derive(Deserialize, Debug)]
 pub struct Config {
+    #[serde(default)]
+    pub server: ServerConfig,
     pub recordings_folder: String,
     pub capture: CaptureConfig,
+    // The capture backends by name, `capture.backend` picks one of them.
+    #[serde(default = "default_backends")]
+    pub backends: HashMap<String, BackendSection>,
     pub keep_alive_timeout_in_secs: u64,
+    #[serde(default)]
+    pub keep_alive_policy: KeepAlivePolicy,
+    #[serde(default)]
+    pub capture_limits: CaptureLimits,
+    #[serde(default)]
+    pub session_limits: SessionLimits,
+    // Without it, the api is open to anyone who can reach it.
+    pub auth: Option<AuthConfig>,
+    #[serde(default)]
+    pub finalization: FinalizationConfig,
+    #[serde(default)]
+    pub webhooks: WebhooksConfig,
+    #[serde(default)]
+    pub audio: AudioSource,
+    // Without it, the recordings stay on disk until they are downloaded or deleted.
+    pub upload: Option<UploadConfig>,
+    // Without it, only a client pauses the recordings.
+    pub focus_watch: Option<FocusWatchConfig>,
+}
+
+// Pauses the current session while the focused window matches one of the deny-list, e.g. a
+// password manager, and resumes it once another window has the focus.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct FocusWatchConfig {
+    pub deny: Vec<WindowPattern>,
+    // Also pauses the audio, which pauses the whole session. Otherwise the video is blacked out
+    // while the audio goes on, so they stay in sync.
+    #[serde(default)]
+    pub pause_audio: bool,
+    #[serde(default = "default_focus_poll_interval")]
+    pub poll_interval_in_ms: u64,
+    // The X11 display whose _NET_ACTIVE_WINDOW is watched, only used on Linux.
+    #[serde(default = "default_focus_display")]
+    pub display: String,
+}
+
+fn default_focus_poll_interval() -> u64 {
+    250
+}
+
+// Every poll runs xprop twice on Linux.
+const MIN_FOCUS_POLL_INTERVAL_IN_MS: u64 = 50;
+
+fn default_focus_display() -> String {
+    ":0".to_string()
+}
+
+// Matches a window by its title, its process or both, see `WindowTarget`.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct WindowPattern {
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub process: Option<String>,
+}
+
+// Where the audio of a session comes from.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default)]
+#[serde(tag = "source", rename_all = "snake_case")]
+pub enum AudioSource {
+    // The default input device.
+    #[default]
+    Device,
+    // A generated tone, for machines without an input device.
+    Sine { frequency_in_hz: f32 },
+    Silence,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct UploadConfig {
+    // Receives every finished recording as a multipart form with a `file` part.
+    pub url: String,
+    pub station_id: String,
+    #[serde(default = "default_upload_interval")]
+    pub interval_in_secs: u64,
+}
+
+fn default_upload_interval() -> u64 {
+    60
+}
+
+// What the recorder does once every keep-alive lease of a session has expired.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExpiryAction {
+    Stop,
+    // Pauses the session, a client has to /resume it.
+    Pause,
+    // Keeps recording, the session is marked as orphaned until a client renews or joins it.
+    MarkOrphaned,
+    // Keeps recording for `grace_in_secs` more, then stops.
+    StopAfterGrace,
 }
 
 #[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(default)]
+pub struct KeepAlivePolicy {
+    pub action: ExpiryAction,
+    pub grace_in_secs: u64,
+    // How long before acting a warning event is emitted.
+    pub warning_in_secs: u64,
+}
+
+impl Default for KeepAlivePolicy {
+    fn default() -> Self {
+        KeepAlivePolicy {
+            action: ExpiryAction::Stop,
+            grace_in_secs: 60,
+            warning_in_secs: 10,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct FinalizationConfig {
+    // How long a stopped capture may take to exit before it is terminated.
+    pub capture_stop_timeout_in_secs: u64,
+}
+
+impl Default for FinalizationConfig {
+    fn default() -> Self {
+        FinalizationConfig {
+            capture_stop_timeout_in_secs: 30,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct WebhooksConfig {
+    pub endpoints: Vec<WebhookEndpoint>,
+    // Where the undelivered events are kept, so they survive restarts.
+    pub queue_path: String,
+    // A delivery is dropped once it failed this many times.
+    pub max_attempts: u32,
+}
+
+impl Default for WebhooksConfig {
+    fn default() -> Self {
+        WebhooksConfig {
+            endpoints: vec![],
+            queue_path: "./webhooks-queue.json".to_string(),
+            max_attempts: 10,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct WebhookEndpoint {
+    pub url: String,
+    // Signs the deliveries with HMAC-SHA256 when present.
+    pub secret: Option<String>,
+    // The event types to deliver, e.g. "session_started", every event when empty.
+    #[serde(default)]
+    pub events: Vec<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct ServerConfig {
+    // The api listens on every one of them, e.g. only on "127.0.0.1".
+    pub addresses: Vec<IpAddr>,
+    pub port: u16,
+    // Serves HTTPS instead of HTTP when present.
+    pub tls: Option<TlsConfig>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            addresses: vec![IpAddr::V4(Ipv4Addr::UNSPECIFIED)],
+            port: 3030,
+            tls: None,
+        }
+    }
+}
+
+// PEM encoded certificate chain and private key.
+#[derive(Deserialize, Debug)]
+pub struct TlsConfig {
+    pub cert_path: String,
+    pub key_path: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct CaptureConfig {
-    pub ffmpeg: bool,
+    // The name of one of the configured backends, the native one when unset.
+    #[serde(default)]
+    pub backend: String,
+    // Deprecated, picks the ffmpeg backend when `backend` is unset.
+    #[serde(default, skip_serializing)]
+    pub ffmpeg: Option<bool>,
     pub bitrate: u32,
     pub fps: u32,
+    // The display to capture, by its index or name in GET /displays, or "all". Unset captures the
+    // primary display.
+    #[serde(default)]
+    pub display: Option<DisplaySelection>,
+    // How the displays are recorded when capturing all of them.
+    #[serde(default)]
+    pub layout: DisplayLayout,
+    // The part of the captured area to record, relative to its top left corner. With a separate
+    // layout it applies to every display.
+    #[serde(default)]
+    pub region: Option<CaptureRegion>,
+    // The size of the video, the recorded area is scaled to it.
+    #[serde(default)]
+    pub output: Option<OutputSize>,
+    #[serde(default)]
+    pub scale: ScaleMode,
+    // A window to capture instead of the displays, `display` and `layout` do not apply to it.
+    #[serde(default)]
+    pub window: Option<WindowTarget>,
+    // Whether the mouse cursor is in the video.
+    #[serde(default = "default_cursor")]
+    pub cursor: bool,
+    // Outlines the captured area on screen while recording.
+    #[serde(default)]
+    pub border: bool,
+    // The least time between two captured frames, only the native backend takes it. Unset lets
+    // Windows decide.
+    #[serde(default)]
+    pub min_update_interval_in_ms: Option<u64>,
+    // Only the native backend takes it.
+    #[serde(default)]
+    pub dirty_region: DirtyRegionMode,
+    // The format Windows hands the frames over in, only the native backend takes it.
+    #[serde(default)]
+    pub color_format: FrameFormat,
+    // Marks the mouse clicks in the video, see `Capabilities::click_highlight`. The x11 backend
+    // needs xinput and xdotool to follow the mouse, the synthetic one makes up its clicks.
+    #[serde(default)]
+    pub click_highlight: Option<ClickHighlight>,
+    // Burns the time and where the video comes from into every frame.
+    #[serde(default)]
+    pub watermark: Option<Watermark>,
+    // Hidden in every frame before it is encoded. The initial masks, a session's masks can be
+    // replaced while it records.
+    #[serde(default)]
+    pub privacy_masks: Vec<PrivacyMask>,
+}
+
+fn default_cursor() -> bool {
+    true
+}
+
+impl CaptureConfig {
+    // Configs from before the backends were pluggable chose between two of them with `ffmpeg`.
+    fn upgrade(&mut self) {
+        if let Some(ffmpeg) = self.ffmpeg.take() {
+            warn!("capture.ffmpeg is deprecated, use capture.backend instead");
+            if self.backend.is_empty() && ffmpeg {
+                self.backend = "ffmpeg".to_string();
+            }
+        }
+        if self.backend.is_empty() {
+            self.backend = "native".to_string();
+        }
+    }
+}
+
+// The wall-clock time with milliseconds, followed by a line for each of the labels.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Watermark {
+    pub station_id: Option<String>,
+    pub session_id: bool,
+    pub text: Option<String>,
+    pub corner: Corner,
+    // The size of the text, 1 is 13 pixels high.
+    pub scale: u32,
+    // The font the ffmpeg backends draw with, fontconfig's monospace font when unset.
+    pub font_file: Option<String>,
+}
+
+impl Default for Watermark {
+    fn default() -> Self {
+        Watermark {
+            station_id: None,
+            session_id: true,
+            text: None,
+            corner: Corner::TopLeft,
+            scale: 2,
+            font_file: None,
+        }
+    }
+}
+
+impl Watermark {
+    // The lines under the time, drawn by every capture backend.
+    #[cfg(any(
+        all(windows, any(feature = "native", feature = "ffmpeg")),
+        feature = "x11",
+        feature = "synthetic"
+    ))]
+    pub fn labels(&self, session_id: &str) -> Vec<String> {
+        let mut labels = vec![];
+        if let Some(station_id) = &self.station_id {
+            labels.push(format!("Station {station_id}"));
+        }
+        if self.session_id {
+            labels.push(format!("Session {session_id}"));
+        }
+        labels.extend(self.text.clone());
+        labels
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Corner {
+    #[default]
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+// An area hidden from the video. The ffmpeg backends black out their capture and restart it when
+// the masks change or a masked window moves, which leaves a short gap in the video, and a window
+// may show for up to a tenth of a second after it moved. A window that starts to match is masked
+// within a fifth of a second, on every backend.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct PrivacyMask {
+    pub area: MaskArea,
+    #[serde(default)]
+    pub style: MaskStyle,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum MaskArea {
+    // On the virtual screen, like the displays in GET /displays.
+    Rect {
+        x: i32,
+        y: i32,
+        width: u32,
+        height: u32,
+    },
+    // Every open window that matches, wherever it is, see `WindowTarget`.
+    Window {
+        #[serde(default)]
+        title: Option<String>,
+        #[serde(default)]
+        process: Option<String>,
+    },
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MaskStyle {
+    #[default]
+    Blackout,
+    // Coarse blocks of the area's average colors.
+    Pixelate,
+}
+
+// How Windows reports the changed parts of the captured frames.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DirtyRegionMode {
+    #[default]
+    Default,
+    ReportOnly,
+    // Also draws the changed parts into the video, to see what Windows considers changed.
+    ReportAndRender,
+}
+
+// The 8-bit formats of the captured frames. RGBA frames are turned into the BGRA the encoder takes,
+// which costs a pass over every frame.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FrameFormat {
+    #[default]
+    Bgra8,
+    Rgba8,
+}
+
+// A ring drawn around the cursor where a mouse button was pressed, fading out over the duration.
+// The ffmpeg backends draw a square instead, which does not fade.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClickHighlight {
+    // In pixels of the captured screen.
+    pub radius: u32,
+    pub duration_in_ms: u64,
+    // Red, green and blue.
+    pub color: [u8; 3],
+    // Also rings the cursor between the clicks, fainter.
+    pub cursor: bool,
+}
+
+impl Default for ClickHighlight {
+    fn default() -> Self {
+        ClickHighlight {
+            radius: 24,
+            duration_in_ms: 500,
+            color: [255, 200, 0],
+            cursor: false,
+        }
+    }
+}
+
+// Picks the window to capture by its title, its process or both.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct WindowTarget {
+    // A regular expression the title has to match, e.g. "- Notepad$".
+    #[serde(default)]
+    pub title: Option<String>,
+    // The name of the executable, e.g. "notepad.exe", ignoring case.
+    #[serde(default)]
+    pub process: Option<String>,
+    #[serde(default)]
+    pub on_closed: WindowClosedAction,
+}
+
+// What the capture does once the window is closed.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WindowClosedAction {
+    // Ends the session.
+    #[default]
+    Stop,
+    // Waits for a matching window and keeps recording into the same video, which shows the last
+    // frame until then.
+    WaitForReopen,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct CaptureRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct OutputSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ScaleMode {
+    // Keeps the aspect ratio, the video is as large as fits in the output size.
+    #[default]
+    Fit,
+    // Fills the output size, distorting the picture if the aspect ratios differ.
+    Stretch,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum DisplaySelection {
+    Index(usize),
+    Name(String),
+}
+
+impl DisplaySelection {
+    pub fn is_all(&self) -> bool {
+        matches!(self, DisplaySelection::Name(name) if name == "all")
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DisplayLayout {
+    // One video as wide as the displays' bounding box.
+    #[default]
+    Composite,
+    // A video per display, the first one is the session's video and the others are suffixed
+    // with `-display{index}`. Each of them is combined with the whole audio.
+    Separate,
+}
+
+// A backend's kind, e.g. "native" or "ffmpeg", and the settings that kind takes.
+#[derive(Deserialize, Debug, Clone)]
+pub struct BackendSection {
+    pub kind: String,
+    #[serde(flatten)]
+    pub settings: Map<String, Value>,
+}
+
+fn default_backends() -> HashMap<String, BackendSection> {
+    ["native", "ffmpeg", "x11", "synthetic"]
+        .into_iter()
+        .map(|kind| {
+            (
+                kind.to_string(),
+                BackendSection {
+                    kind: kind.to_string(),
+                    settings: Map::new(),
+                },
+            )
+        })
+        .collect()
+}
+
+// Bounds for the capture settings a /start request may ask for.
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct CaptureLimits {
+    pub max_fps: u32,
+    pub max_bitrate: u32,
+    // Also bounds the configured `keep_alive_timeout_in_secs`.
+    #[serde(default = "default_max_keep_alive_timeout")]
+    pub max_keep_alive_timeout_in_secs: u64,
+}
+
+impl Default for CaptureLimits {
+    fn default() -> Self {
+        CaptureLimits {
+            max_fps: 60,
+            max_bitrate: 50_000_000,
+            max_keep_alive_timeout_in_secs: default_max_keep_alive_timeout(),
+        }
+    }
+}
+
+fn default_max_keep_alive_timeout() -> u64 {
+    24 * 60 * 60
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    // May only read the recorder status and the list of recordings.
+    ReadOnly,
+    // May also start, stop and keep recordings alive.
+    Operator,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LimitAction {
+    // Ends the session.
+    Stop,
+    // Finalizes the outputs so far as a segment and keeps recording into the next one.
+    Rotate,
+}
+
+// Bounds for a single segment of a session, no limit when absent.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy)]
+#[serde(default)]
+pub struct SessionLimits {
+    // Recorded time, not counting pauses.
+    pub max_duration_in_secs: Option<u64>,
+    // The video and audio outputs together.
+    pub max_size_in_bytes: Option<u64>,
+    pub on_limit: LimitAction,
+}
+
+impl Default for SessionLimits {
+    fn default() -> Self {
+        SessionLimits {
+            max_duration_in_secs: None,
+            max_size_in_bytes: None,
+            on_limit: LimitAction::Stop,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct AuthConfig {
+    pub clients: Vec<AuthClient>,
+    #[serde(default = "default_max_clock_skew")]
+    pub max_clock_skew_in_secs: u64,
+}
+
+// A client authenticates either with a bearer token or by signing its requests with a shared secret.
+#[derive(Deserialize, Debug)]
+pub struct AuthClient {
+    pub name: String,
+    pub role: Role,
+    pub token: Option<String>,
+    pub hmac_secret: Option<String>,
+}
+
+fn default_max_clock_skew() -> u64 {
+    300
 }
 
 pub fn get_config() -> Result<Config, Box<dyn std::error::Error>> {
-    Ok(Config::from_config_file("config.json")?)
+    let mut config = Config::from_config_file("config.json")?;
+    config.capture.upgrade();
+    config.validate()?;
+
+    Ok(config)
+}
+
+impl Config {
+    // What the types can not tell, so the recorder does not start with it.
+    fn validate(&self) -> Result<(), anyhow::Error> {
+        if self.server.addresses.is_empty() {
+            return Err(anyhow::Error::msg(
+                "server.addresses must hold at least one address",
+            ));
+        }
+        // A zero interval would make the uploader's timer panic.
+        if self
+            .upload
+            .as_ref()
+            .is_some_and(|upload| upload.interval_in_secs == 0)
+        {
+            return Err(anyhow::Error::msg(
+                "upload.interval_in_secs must be at least 1",
+            ));
+        }
+        if let Some(focus_watch) = &self.focus_watch {
+            if focus_watch.poll_interval_in_ms < MIN_FOCUS_POLL_INTERVAL_IN_MS {
+                return Err(anyhow::Error::msg(format!(
+                    "focus_watch.poll_interval_in_ms must be at least {MIN_FOCUS_POLL_INTERVAL_IN_MS}"
+                )));
+            }
+            for (index, pattern) in focus_watch.deny.iter().enumerate() {
+                WindowMatcher::new(pattern.title.as_deref(), pattern.process.as_deref())
+                    .map_err(|err| {
+                        anyhow::Error::msg(format!("focus_watch.deny[{index}]: {err}"))
+                    })?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn upgraded(capture: Value) -> CaptureConfig {
+        let mut capture: CaptureConfig = serde_json::from_value(capture).unwrap();
+        capture.upgrade();
+        capture
+    }
+
+    #[test]
+    fn picks_ffmpeg_from_the_deprecated_flag() {
+        let capture = upgraded(json!({ "ffmpeg": true, "bitrate": 5000000, "fps": 15 }));
+        assert_eq!(capture.backend, "ffmpeg");
+        assert_eq!(capture.ffmpeg, None);
+        let serialized = serde_json::to_value(&capture).unwrap();
+        assert!(serialized.get("ffmpeg").is_none());
+
+        let capture = upgraded(json!({ "ffmpeg": false, "bitrate": 5000000, "fps": 15 }));
+        assert_eq!(capture.backend, "native");
+    }
+
+    #[test]
+    fn prefers_the_backend_over_the_deprecated_flag() {
+        let capture = upgraded(json!({
+            "backend": "x11",
+            "ffmpeg": true,
+            "bitrate": 5000000,
+            "fps": 15
+        }));
+        assert_eq!(capture.backend, "x11");
+        assert_eq!(capture.ffmpeg, None);
+    }
+
+    #[test]
+    fn defaults_to_the_native_backend() {
+        let capture = upgraded(json!({ "bitrate": 5000000, "fps": 15 }));
+        assert_eq!(capture.backend, "native");
+    }
 }
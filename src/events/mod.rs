@@ -0,0 +1,87 @@
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+use crate::config::ExpiryAction;
+
+// How many events a slow subscriber may fall behind before it starts missing some.
+const EVENTS_CAPACITY: usize = 256;
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EventKind {
+    SessionStarted,
+    SessionPaused,
+    SessionResumed,
+    // Only emitted by the backends that see frames.
+    #[cfg_attr(
+        not(any(all(windows, feature = "native"), feature = "synthetic")),
+        allow(dead_code)
+    )]
+    FirstFrameReceived,
+    AudioDeviceOpened { device: String },
+    // The keep-alive policy is about to act on the session.
+    KeepAliveWarning { action: ExpiryAction, acts_in_secs: u64 },
+    KeepAliveExpired { action: ExpiryAction },
+    // A client renewed or joined an orphaned session.
+    SessionAdopted,
+    StopRequested,
+    CombineFinished { output: String },
+    CombineFailed { error: String },
+    // The captured window is not open, the capture waits for it.
+    #[cfg_attr(not(all(windows, feature = "native")), allow(dead_code))]
+    WaitingForWindow,
+    #[cfg_attr(not(all(windows, feature = "native")), allow(dead_code))]
+    WindowFound { title: String },
+    #[cfg_attr(not(all(windows, feature = "native")), allow(dead_code))]
+    CaptureWindowClosed,
+    // The privacy masks of a session were replaced through the api, how many there are now.
+    PrivacyMasksChanged { masks: usize },
+    // The focused window matched a rule of the focus watch deny-list, see `focus::focus_task`.
+    FocusPaused {
+        rule: usize,
+        process: String,
+        audio: bool,
+    },
+    FocusResumed,
+    // A session limit was reached and the outputs moved to a new segment.
+    SegmentRotated { segment: usize },
+    // A session limit was reached and the session is stopping.
+    LimitReached { reason: String },
+    // An uploaded marker appeared next to the recording, see `recordings::watch_uploads`.
+    UploadFinished { recording: String },
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Event {
+    pub session_id: Option<String>,
+    pub at: DateTime<Local>,
+    #[serde(flatten)]
+    pub kind: EventKind,
+}
+
+// Fans the recorder events out to every subscriber, can be cloned into the capture threads.
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<Event>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(EVENTS_CAPACITY);
+        EventBus { sender }
+    }
+
+    pub fn emit(&self, session_id: Option<&str>, kind: EventKind) {
+        // Sending only fails when nobody is subscribed, which is fine.
+        let _ = self.sender.send(Event {
+            session_id: session_id.map(str::to_string),
+            at: Local::now(),
+            kind,
+        });
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.sender.subscribe()
+    }
+}
@@ -1,63 +1,195 @@
 use anyhow::Error;
-use log::{info, error};
+use serde::Deserialize;
+use serde_json::{Map, Value};
 use std::{
-    io::Write, process::{Command, Stdio}, sync::{Arc, Mutex}, thread, time::Duration
+    process::{Command, Stdio},
+    sync::Arc,
 };
-use windows_capture::monitor::Monitor;
 
-use crate::config::CaptureConfig;
+use crate::{
+    capture::{
+        displays::{self, DisplayInfo},
+        masks::MaskRect,
+        mouse::MouseTracker,
+        Capabilities, CaptureBackend,
+    },
+    config::CaptureConfig,
+    ffmpeg::{
+        clicks::{click_filter, ClickBoxes, OutputArea},
+        drawtext::watermark_filter,
+        masks::mask_filter,
+        parts::capture_in_parts,
+    },
+    session::Session,
+};
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+struct FfmpegSettings {
+    // May be a name on the PATH.
+    executable: String,
+    // How much of the input ffmpeg reads to detect its format, e.g. "10M".
+    probesize: Option<String>,
+}
+
+impl Default for FfmpegSettings {
+    fn default() -> Self {
+        FfmpegSettings {
+            executable: "ffmpeg.exe".to_string(),
+            probesize: Some("10M".to_string()),
+        }
+    }
+}
+
+// Captures the selected monitors with ffmpeg's gdigrab.
+struct FfmpegBackend {
+    settings: FfmpegSettings,
+}
+
+pub fn backend(settings: Map<String, Value>) -> Result<Arc<dyn CaptureBackend>, anyhow::Error> {
+    Ok(Arc::new(FfmpegBackend {
+        settings: serde_json::from_value(Value::Object(settings))?,
+    }))
+}
 
-pub fn ffmpeg_capture(
-    recording: Arc<Mutex<bool>>,
-    recording_raw: Arc<Mutex<bool>>,
+impl CaptureBackend for FfmpegBackend {
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            pause: true,
+            rotation: true,
+            frame_stats: false,
+            window: false,
+            click_highlight: true,
+            window_masks: true,
+        }
+    }
+
+    fn start(
+        &self,
+        session: Arc<Session>,
+        filename: String,
+        capture_config: CaptureConfig,
+    ) -> Result<(), anyhow::Error> {
+        ffmpeg_capture(session, filename, capture_config, self.settings.clone())
+    }
+
+    fn displays(&self) -> Result<Vec<DisplayInfo>, anyhow::Error> {
+        Ok(displays::monitors()?
+            .into_iter()
+            .map(|(display, _)| display)
+            .collect())
+    }
+}
+
+fn ffmpeg_capture(
+    session: Arc<Session>,
     filename: String,
     capture_config: CaptureConfig,
+    settings: FfmpegSettings,
 ) -> Result<(), anyhow::Error> {
-    let primary_monitor = Monitor::primary()?;
-    let width = primary_monitor.width()?;
-    let height = primary_monitor.height()?;
-    let fps = capture_config.fps;
-
-    let child = Command::new("cmd")
-        .args([
-            "/C",
-            &format!("ffmpeg.exe -video_size {width}x{height} -probesize 10M -f gdigrab -framerate {fps} -i desktop {filename}"),
-        ])
-        .stdin(Stdio::piped())
-        .spawn()
-        .or(Err(Error::msg("Could not start ffmpeg capture")))?;
-
-    *recording.lock().unwrap() = true;
-    *recording_raw.lock().unwrap() = true;
-    info!("Starting capture via ffmpeg.exe");
-
-    let child = Arc::new(Mutex::new(child));
-
-    thread::spawn(move || {
-        loop {
-            if !*recording.lock().unwrap() {
-                match (*child.lock().unwrap()).stdin.as_mut() {
-                    Some(stdin) => {
-                        info!("Writing q");
-                        let _ = stdin.write_all("q".as_bytes());
-                        stdin.flush().unwrap();
-                        break;
-                    },
-                    None => {
-                        error!("Could not stop ffmpeg.exe, stdin is not present");
-                        ()
-                    }
-                };
-                info!("Done with capture");
-            }
-
-            thread::sleep(Duration::from_millis(100));
-        }
-
-        let _ = child.lock().unwrap().wait();
-        *recording.lock().unwrap() = false;
-        *recording_raw.lock().unwrap() = false;
+    let selected = displays::select(
+        displays::monitors()?
+            .into_iter()
+            .map(|(display, _)| display)
+            .collect(),
+        capture_config.display.as_ref(),
+    )?;
+    let regions = displays::regions(&selected, capture_config.layout)
+        .iter()
+        .map(|region| region.crop(capture_config.region.as_ref()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let suffixes = regions.iter().map(|region| region.suffix.clone()).collect();
+    let fps = capture_config.fps.to_string();
+    let bitrate = capture_config.bitrate.to_string();
+    let executable = settings.executable;
+    let mut options = vec![
+        "-draw_mouse".to_string(),
+        (capture_config.cursor as u8).to_string(),
+        "-show_region".to_string(),
+        (capture_config.border as u8).to_string(),
+    ];
+    if let Some(probesize) = settings.probesize {
+        options.extend(["-probesize".to_string(), probesize]);
+    }
+    let scales: Vec<Option<String>> = regions
+        .iter()
+        .map(|region| {
+            let (width, height) = displays::scaled_size(
+                region.width,
+                region.height,
+                capture_config.output,
+                capture_config.scale,
+            );
+            ((width, height) != (region.width, region.height))
+                .then(|| format!("scale={width}:{height}"))
+        })
+        .collect();
+    let click_filters: Vec<Option<String>> = (0..regions.len())
+        .map(|output| {
+            capture_config
+                .click_highlight
+                .map(|highlight| click_filter(&highlight, output))
+        })
+        .collect();
+    let clicks = capture_config.click_highlight.map(|highlight| {
+        let outputs = regions
+            .iter()
+            .map(|region| OutputArea {
+                origin: (region.x, region.y),
+                size: Some((region.width, region.height)),
+            })
+            .collect();
+        ClickBoxes::new(highlight, MouseTracker::start(session.clone()), outputs)
     });
+    let watermark = capture_config.watermark;
+    let session_id = session.id.clone();
+
+    let start_part = move |parts: &[String], masks: &[MaskRect]| {
+        // Built per part, the watermark's time of day depends on the day it starts.
+        let watermark = watermark
+            .as_ref()
+            .map(|watermark| watermark_filter(watermark, &session_id));
+        let mut command = Command::new(&executable);
+        // gdigrab offsets are relative to the primary monitor, like the display positions. Every
+        // region is an input of its own, mapped to the output with the same index.
+        for region in &regions {
+            command
+                .args(["-f", "gdigrab", "-framerate", &fps])
+                .args(["-offset_x", &region.x.to_string()])
+                .args(["-offset_y", &region.y.to_string()])
+                .args([
+                    "-video_size",
+                    &format!("{}x{}", region.width, region.height),
+                ])
+                .args(&options)
+                .args(["-i", "desktop"]);
+        }
+        for (input, part) in parts.iter().enumerate() {
+            let region = &regions[input];
+            let masks = mask_filter(
+                masks,
+                (region.x, region.y),
+                Some((region.width, region.height)),
+            );
+            // The clicks before the masks, so a blacked out part hides them too.
+            let filters: Vec<&str> = [
+                click_filters[input].as_deref(),
+                Some(masks.as_str()),
+                scales[input].as_deref(),
+                watermark.as_deref(),
+            ]
+            .into_iter()
+            .flatten()
+            .collect();
+            command.args(["-map", &input.to_string()]);
+            command.args(["-vf", &filters.join(",")]);
+            command.args(["-b:v", &bitrate, "-pix_fmt", "yuv420p", "-y", part]);
+        }
+        command
+            .stdin(Stdio::piped())
+            .spawn()
+            .or(Err(Error::msg("Could not start ffmpeg capture")))
+    };
 
-    Ok(())
+    capture_in_parts(session, filename, suffixes, clicks, start_part)
 }
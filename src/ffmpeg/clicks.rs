@@ -0,0 +1,201 @@
+use std::{
+    process::Child,
+    time::{Duration, Instant},
+};
+
+use super::parts::send_command;
+use crate::{capture::mouse::MouseTracker, config::ClickHighlight};
+
+// ffmpeg reads a key from stdin every tenth of a second, so every command takes that long.
+const COMMAND_INTERVAL: Duration = Duration::from_millis(100);
+
+// The square around the cursor between the clicks.
+const CURSOR_OPACITY: f32 = 0.35;
+
+// The disabled squares of an output, before it is scaled so they are as large as on the screen.
+// Every output has filters of its own, they are moved to where the output shows the mouse.
+pub fn click_filter(highlight: &ClickHighlight, output: usize) -> String {
+    let [red, green, blue] = highlight.color;
+    let size = highlight.radius * 2;
+    let stroke = (highlight.radius / 6).max(2);
+    let square = |name: &str, opacity: f32| {
+        format!(
+            "drawbox@{name}{output}=w={size}:h={size}:t={stroke}:\
+             color=0x{red:02x}{green:02x}{blue:02x}@{opacity}:enable=0"
+        )
+    };
+
+    match highlight.cursor {
+        true => format!(
+            "{},{}",
+            square("cursor", CURSOR_OPACITY),
+            square("click", 1.0)
+        ),
+        false => square("click", 1.0),
+    }
+}
+
+// Where an output is on the virtual screen, its size is unknown on a bare X11 screen.
+pub struct OutputArea {
+    pub origin: (i32, i32),
+    pub size: Option<(u32, u32)>,
+}
+
+// A drawbox filter in every output, shown in those the mouse is in.
+struct Square {
+    name: &'static str,
+    shown: Vec<bool>,
+}
+
+// Draws the mouse clicks into the parts of an ffmpeg capture, as squares that are moved with
+// commands through ffmpeg's stdin. Unlike the rings of the native backend they do not fade, show
+// up to a few tenths of a second late, and the cursor's square follows it only every so often.
+pub struct ClickBoxes {
+    highlight: ClickHighlight,
+    mouse: MouseTracker,
+    outputs: Vec<OutputArea>,
+    click: Square,
+    cursor: Square,
+    // The part the squares are in, by its process. A new part starts with them hidden.
+    part: Option<u32>,
+    // The click shown, by when it happened.
+    shown_click: Option<Instant>,
+    shown_cursor: Option<(i32, i32)>,
+    // Until ffmpeg got to the commands sent so far.
+    busy_until: Instant,
+}
+
+impl ClickBoxes {
+    pub fn new(highlight: ClickHighlight, mouse: MouseTracker, outputs: Vec<OutputArea>) -> Self {
+        let square = |name| Square {
+            name,
+            shown: vec![false; outputs.len()],
+        };
+        ClickBoxes {
+            highlight,
+            mouse,
+            click: square("click"),
+            cursor: square("cursor"),
+            outputs,
+            part: None,
+            shown_click: None,
+            shown_cursor: None,
+            busy_until: Instant::now(),
+        }
+    }
+
+    // Moves the squares of the running part to the mouse, called whenever the parts loop checks.
+    pub fn follow(&mut self, child: &mut Child) {
+        if self.part != Some(child.id()) {
+            self.part = Some(child.id());
+            self.click.shown.fill(false);
+            self.cursor.shown.fill(false);
+            self.shown_click = None;
+            self.shown_cursor = None;
+        }
+        let mouse = self.mouse.current();
+        let duration = Duration::from_millis(self.highlight.duration_in_ms);
+        let radius = self.highlight.radius as i32;
+
+        match mouse.clicked.filter(|(_, at)| at.elapsed() < duration) {
+            Some((position, at)) if self.shown_click != Some(at) => {
+                self.shown_click = Some(at);
+                let sent = self
+                    .click
+                    .show(child, &self.outputs, radius, Some(position));
+                self.sent(sent);
+            }
+            None if self.shown_click.is_some() => {
+                self.shown_click = None;
+                let sent = self.click.show(child, &self.outputs, radius, None);
+                self.sent(sent);
+            }
+            _ => {}
+        }
+
+        // Moving the cursor's square while ffmpeg is still busy would only queue up commands.
+        if self.highlight.cursor
+            && mouse.cursor != self.shown_cursor
+            && Instant::now() >= self.busy_until
+        {
+            self.shown_cursor = mouse.cursor;
+            let sent = self.cursor.show(child, &self.outputs, radius, mouse.cursor);
+            self.sent(sent);
+        }
+    }
+
+    // ffmpeg gets to the commands one after the other.
+    fn sent(&mut self, commands: usize) {
+        self.busy_until = self.busy_until.max(Instant::now()) + COMMAND_INTERVAL * commands as u32;
+    }
+}
+
+impl Square {
+    // Moves the square to `position` in the outputs it is in and hides it in the others, returns
+    // how many commands that took.
+    fn show(
+        &mut self,
+        child: &mut Child,
+        outputs: &[OutputArea],
+        radius: i32,
+        position: Option<(i32, i32)>,
+    ) -> usize {
+        let mut commands = vec![];
+        for (output, (area, shown)) in outputs.iter().zip(&mut self.shown).enumerate() {
+            let target = format!("drawbox@{}{output}", self.name);
+            let inside = position.filter(|&(x, y)| match area.size {
+                Some((width, height)) => {
+                    (area.origin.0 - radius..area.origin.0 + width as i32 + radius).contains(&x)
+                        && (area.origin.1 - radius..area.origin.1 + height as i32 + radius)
+                            .contains(&y)
+                }
+                None => true,
+            });
+            match inside {
+                Some((x, y)) => {
+                    commands.push((target.clone(), format!("x {}", x - area.origin.0 - radius)));
+                    commands.push((target.clone(), format!("y {}", y - area.origin.1 - radius)));
+                    if !*shown {
+                        commands.push((target, "enable 1".to_string()));
+                    }
+                    *shown = true;
+                }
+                None if *shown => {
+                    commands.push((target, "enable 0".to_string()));
+                    *shown = false;
+                }
+                None => {}
+            }
+        }
+
+        // A part that is ending takes no commands anymore, the next one shows the mouse again.
+        for (target, command) in &commands {
+            let _ = send_command(child, target, command);
+        }
+        commands.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn draws_hidden_squares_around_the_clicks() {
+        let highlight = ClickHighlight::default();
+        assert_eq!(
+            click_filter(&highlight, 1),
+            "drawbox@click1=w=48:h=48:t=4:color=0xffc800@1:enable=0"
+        );
+
+        let highlight = ClickHighlight {
+            cursor: true,
+            ..highlight
+        };
+        assert_eq!(
+            click_filter(&highlight, 0),
+            "drawbox@cursor0=w=48:h=48:t=4:color=0xffc800@0.35:enable=0,\
+             drawbox@click0=w=48:h=48:t=4:color=0xffc800@1:enable=0"
+        );
+    }
+}
@@ -0,0 +1,93 @@
+use chrono::{Local, NaiveTime};
+
+use crate::config::{Corner, Watermark};
+
+// Close to the 13 pixel high font the other backends draw the watermark with.
+const FONT_SIZE: u32 = 12;
+const LINE_HEIGHT: u32 = 15;
+
+// The filters burning the watermark in, a drawtext per line. The frames are stamped with the
+// wall-clock time while they pass, then get their timestamps back.
+pub fn watermark_filter(watermark: &Watermark, session_id: &str) -> String {
+    // hms shows the time since midnight, it is built when ffmpeg starts so the day is known.
+    let now = Local::now();
+    let midnight = now
+        .with_time(NaiveTime::MIN)
+        .earliest()
+        .unwrap_or(now)
+        .timestamp();
+    let time = format!("%{{pts:localtime:0:%Y-%m-%d}} %{{pts:hms:-{midnight}}}");
+    let lines: Vec<String> = std::iter::once(time)
+        .chain(
+            watermark
+                .labels(session_id)
+                .iter()
+                .map(|label| escape(label, "\\%")),
+        )
+        .collect();
+
+    let scale = watermark.scale;
+    let border = 2 * scale;
+    let font = match &watermark.font_file {
+        Some(path) => format!("fontfile={}", escape_option(path)),
+        None => "font=monospace".to_string(),
+    };
+    let drawtexts = lines.iter().enumerate().map(|(line, text)| {
+        let x = match watermark.corner {
+            Corner::TopLeft | Corner::BottomLeft => border.to_string(),
+            Corner::TopRight | Corner::BottomRight => format!("w-tw-{border}"),
+        };
+        let above = border + line as u32 * LINE_HEIGHT * scale;
+        let below = border + (lines.len() - line) as u32 * LINE_HEIGHT * scale;
+        let y = match watermark.corner {
+            Corner::TopLeft | Corner::TopRight => above.to_string(),
+            Corner::BottomLeft | Corner::BottomRight => format!("h-{below}"),
+        };
+        format!(
+            "drawtext={font}:text={}:fontsize={}:fontcolor=white:x={x}:y={y}:box=1:boxcolor=black@0.6:boxborderw={border}",
+            escape_option(text),
+            FONT_SIZE * scale
+        )
+    });
+
+    std::iter::once("setpts=RTCTIME/1000000/TB".to_string())
+        .chain(drawtexts)
+        .chain(std::iter::once("setpts=PTS-STARTPTS".to_string()))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+// Escapes an option value for the option parser, then for the filtergraph parser.
+fn escape_option(value: &str) -> String {
+    escape(&escape(value, "\\':"), "\\'[],;")
+}
+
+fn escape(value: &str, special: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for character in value.chars() {
+        if special.contains(character) {
+            escaped.push('\\');
+        }
+        escaped.push(character);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leaves_plain_values_alone() {
+        assert_eq!(escape_option("Station 7"), "Station 7");
+    }
+
+    #[test]
+    fn escapes_for_both_parsers() {
+        // The option parser unescapes `\:`, the filtergraph parser `\\` before it.
+        assert_eq!(escape_option("10:00"), r"10\\:00");
+        assert_eq!(escape_option("it's"), r"it\\\'s");
+        assert_eq!(escape_option(r"C:\fonts"), r"C\\:\\\\fonts");
+        assert_eq!(escape_option("[a],b;c"), r"\[a\]\,b\;c");
+    }
+}
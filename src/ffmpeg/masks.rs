@@ -0,0 +1,105 @@
+use crate::{
+    capture::masks::{MaskRect, BLOCK_SIZE},
+    config::MaskStyle,
+};
+
+// Blacks out the whole frame once a command enables it, see `parts::hide_part`.
+pub const HIDE_TARGET: &str = "drawbox@hide";
+
+// The filters hiding the masks in an area of the virtual screen that starts at `origin`, before it
+// is scaled. Without the area's size, e.g. on a bare X11 screen, the masks are only blacked out,
+// which drawbox clips to the frame. They start with the disabled `HIDE_TARGET`.
+pub fn mask_filter(masks: &[MaskRect], origin: (i32, i32), size: Option<(u32, u32)>) -> String {
+    let hide = format!("{HIDE_TARGET}=color=black:t=fill:enable=0");
+    let filters = masks.iter().enumerate().filter_map(|(index, mask)| {
+        // Without the size, as far as drawbox takes ints.
+        let (width, height) = size.unwrap_or((i32::MAX as u32, i32::MAX as u32));
+        let (left, top, right, bottom) = mask.within(origin);
+        let (right, bottom) = (right.min(width as usize), bottom.min(height as usize));
+        if left >= right || top >= bottom {
+            return None;
+        }
+        let (width, height) = (right - left, bottom - top);
+
+        Some(match (mask.style, size) {
+            // Scaled down to a pixel per block and back up, over a copy of the frame.
+            (MaskStyle::Pixelate, Some(_)) => format!(
+                "split[frame{index}][area{index}];\
+                     [area{index}]crop={width}:{height}:{left}:{top},\
+                     scale={}:{}:flags=area,scale={width}:{height}:flags=neighbor[blocks{index}];\
+                     [frame{index}][blocks{index}]overlay={left}:{top}",
+                width.div_ceil(BLOCK_SIZE),
+                height.div_ceil(BLOCK_SIZE)
+            ),
+            _ => format!("drawbox=x={left}:y={top}:w={width}:h={height}:color=black:t=fill"),
+        })
+    });
+
+    std::iter::once(hide)
+        .chain(filters)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HIDE: &str = "drawbox@hide=color=black:t=fill:enable=0";
+
+    fn mask(x: i32, y: i32, width: u32, height: u32, style: MaskStyle) -> MaskRect {
+        MaskRect {
+            x,
+            y,
+            width,
+            height,
+            style,
+        }
+    }
+
+    #[test]
+    fn starts_with_the_disabled_blackout() {
+        assert_eq!(mask_filter(&[], (0, 0), Some((1920, 1080))), HIDE);
+    }
+
+    #[test]
+    fn blacks_out_the_masks_within_the_area() {
+        let masks = [
+            mask(1950, 10, 100, 50, MaskStyle::Blackout),
+            // Cut where the area starts and ends.
+            mask(1900, 1000, 100, 200, MaskStyle::Blackout),
+            // Left of the area.
+            mask(0, 0, 100, 100, MaskStyle::Blackout),
+        ];
+        assert_eq!(
+            mask_filter(&masks, (1920, 0), Some((1920, 1080))),
+            format!(
+                "{HIDE},\
+                 drawbox=x=30:y=10:w=100:h=50:color=black:t=fill,\
+                 drawbox=x=0:y=1000:w=80:h=80:color=black:t=fill"
+            )
+        );
+    }
+
+    #[test]
+    fn pixelates_over_a_copy_of_the_frame() {
+        let masks = [mask(20, 30, 40, 17, MaskStyle::Pixelate)];
+        assert_eq!(
+            mask_filter(&masks, (0, 0), Some((1920, 1080))),
+            format!(
+                "{HIDE},split[frame0][area0];\
+                 [area0]crop=40:17:20:30,scale=3:2:flags=area,scale=40:17:flags=neighbor[blocks0];\
+                 [frame0][blocks0]overlay=20:30"
+            )
+        );
+    }
+
+    #[test]
+    fn only_blacks_out_without_the_size() {
+        let masks = [mask(20, 30, 40, 17, MaskStyle::Pixelate)];
+        assert_eq!(
+            mask_filter(&masks, (0, 0), None),
+            format!("{HIDE},drawbox=x=20:y=30:w=40:h=17:color=black:t=fill")
+        );
+    }
+}
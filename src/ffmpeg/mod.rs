@@ -2,37 +2,118 @@ use std::process::{Command};
 use anyhow::Error;
 use log::{info, warn};
 
+// gdigrab needs the monitor size from windows-capture.
+#[cfg(all(windows, feature = "ffmpeg"))]
 pub mod capture;
+#[cfg(any(all(windows, feature = "ffmpeg"), feature = "x11"))]
+mod clicks;
+#[cfg(any(all(windows, feature = "ffmpeg"), feature = "x11"))]
+mod drawtext;
+#[cfg(any(all(windows, feature = "ffmpeg"), feature = "x11"))]
+mod masks;
+#[cfg(any(all(windows, feature = "ffmpeg"), feature = "x11"))]
+pub mod parts;
+#[cfg(feature = "x11")]
+pub mod x11;
 
+// Expected on the PATH. The arguments are passed as they are, so the paths need no quoting.
+#[cfg(windows)]
+const FFMPEG: &str = "ffmpeg.exe";
+#[cfg(not(windows))]
+const FFMPEG: &str = "ffmpeg";
 
-pub fn combine_outputs(filename: &str) -> Result<(), anyhow::Error> {
-    let video = format!("{}.mp4", filename);
+
+// Returns the paths of the final outputs, a video for each of `suffixes` combined with the audio.
+// A single file when there is nothing to combine.
+pub fn combine_outputs(filename: &str, suffixes: &[String]) -> Result<Vec<String>, anyhow::Error> {
     let audio = format!("{}.wav", filename);
+    let videos: Vec<(&String, String)> = suffixes
+        .iter()
+        .map(|suffix| (suffix, format!("{}{}.mp4", filename, suffix)))
+        .filter(|(_, video)| std::fs::exists(video).is_ok_and(|exists| exists))
+        .collect();
+
+    if videos.is_empty() {
+        warn!("Not combining, there is no video file present");
+        return Ok(vec![audio])
+    }
 
     if !std::fs::exists(&audio).is_ok_and(|exists| exists) {
         warn!("Not combining, there is no audio file present");
-        return Ok(())
+        return Ok(videos.into_iter().map(|(_, video)| video).collect())
+    }
+
+    // Every video gets the whole audio, the audio is removed once all of them have it.
+    let mut outputs = vec![];
+    for (suffix, video) in &videos {
+        let output = format!("{}{}-combined.mp4", filename, suffix);
+        let mut out = Command::new(FFMPEG)
+            .args(["-y", "-i", video, "-i", &audio, "-c", "copy", &output])
+            .spawn()
+            .or(Err(Error::msg("Could not combine files")))?;
+
+        let code = out.wait()?;
+
+        if !code.success() {
+            return Err(Error::msg("Could not combine outputs via ffmpeg"));
+        }
+        outputs.push(output);
     }
 
-    let output = format!("{}-combined.mp4", filename);
-    let mut out = Command::new("cmd")
-        .args([
-            "/C",
-            &format!("ffmpeg.exe -i {video} -i {audio} -c copy {output}"),
-        ])
+    for (_, video) in videos {
+        std::fs::remove_file(video)?;
+    }
+    std::fs::remove_file(audio)?;
+
+    info!("Done combining via ffmpeg");
+
+    Ok(outputs)
+}
+
+
+// Joins the parts of a paused capture into a single file, without re-encoding.
+#[cfg(any(all(windows, feature = "ffmpeg"), feature = "x11"))]
+pub fn concat_parts(parts: &[String], output: &str) -> Result<(), anyhow::Error> {
+    match parts {
+        [] => return Ok(()),
+        [part] => {
+            std::fs::rename(part, output)?;
+            return Ok(());
+        }
+        _ => (),
+    }
+
+    let list = format!("{output}.parts.txt");
+    let entries: Vec<String> = parts
+        .iter()
+        .map(|part| {
+            let name = std::path::Path::new(part)
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or(part.clone());
+            // Quoted for the concat demuxer, a quote ends the quoting and is escaped outside of it.
+            format!("file '{}'", name.replace('\'', "'\\''"))
+        })
+        .collect();
+    std::fs::write(&list, entries.join("\n"))?;
+
+    let mut out = Command::new(FFMPEG)
+        .args(["-y", "-f", "concat", "-safe", "0", "-i", &list, "-c", "copy", output])
         .spawn()
-        .or(Err(Error::msg("Could not combine files")))?;
+        .or(Err(Error::msg("Could not concatenate parts")))?;
 
     let code = out.wait()?;
 
     if !code.success() {
-        return Err(Error::msg("Could not combine outputs via ffmpeg.exe"));
+        return Err(Error::msg("Could not concatenate parts via ffmpeg"));
     }
 
-    std::fs::remove_file(video)?;
-    std::fs::remove_file(audio)?;
+    std::fs::remove_file(list)?;
+    for part in parts {
+        std::fs::remove_file(part)?;
+    }
 
-    info!("Done combining via ffmpeg.exe");
+    info!("Done concatenating {} parts via ffmpeg", parts.len());
 
     Ok(())
 }
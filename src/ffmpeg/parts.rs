@@ -0,0 +1,200 @@
+use anyhow::Error;
+use log::{info, error, warn};
+use std::{
+    io::Write, process::Child, sync::Arc, thread, time::Duration
+};
+
+use crate::{
+    capture::masks::{MaskRect, MaskTracker},
+    ffmpeg::{self, clicks::ClickBoxes, masks::HIDE_TARGET},
+    session::Session,
+};
+
+// How often the loop looks at the session and the masks.
+const CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+// ffmpeg can not pause a capture, so every pause ends the current part and every resume starts a
+// new one with `start_part`. The parts are concatenated into `filename` once the capture is done,
+// or into the segment's video whenever the session rotates.
+//
+// A capture may write several outputs at once, one per suffix of `filename`, and `start_part`
+// gets the part of each of them. It also gets the privacy masks, which its filters hide until
+// they change. The part is then blacked out while it ends, and the next one hides the new masks.
+// Unlike the native backend, which looks at the masks for every frame, they are looked at every
+// `CHECK_INTERVAL`, so a masked window that moves shows where it moved to for up to that long.
+// The clicks are moved into the part's filters as often.
+pub fn capture_in_parts(
+    session: Arc<Session>,
+    filename: String,
+    suffixes: Vec<String>,
+    mut clicks: Option<ClickBoxes>,
+    start_part: impl Fn(&[String], &[MaskRect]) -> Result<Child, anyhow::Error> + Send + 'static,
+) -> Result<(), anyhow::Error> {
+    let mut filename = filename;
+    let mut segment = session.segment();
+    let tracker = MaskTracker::start(session.clone());
+    let mut masks = tracker.current();
+    let mut parts = vec![part_filenames(&filename, &suffixes, 0)];
+    let mut child = Some(start_part(&parts[0], &masks)?);
+
+    session.set_video_suffixes(suffixes.clone());
+    session.set_video_running(true);
+    info!("Starting capture via ffmpeg");
+
+    thread::spawn(move || {
+        loop {
+            if !session.is_capturing() {
+                if let Some(child) = child.take() {
+                    stop_part(child, &session);
+                }
+                info!("Done with capture");
+                break;
+            }
+
+            let current = session.segment();
+            if current != segment {
+                // The next segment starts before the previous one is stopped, so nothing is lost
+                // in between.
+                let mut previous = child.take();
+                let latest = tracker.current();
+                if latest != masks {
+                    previous.iter_mut().for_each(hide_part);
+                }
+                let previous_parts = std::mem::take(&mut parts);
+                let previous_filename = std::mem::replace(
+                    &mut filename,
+                    format!("{}.mp4", session.segment_filename(current)),
+                );
+                if !session.is_paused() {
+                    let part = part_filenames(&filename, &suffixes, 0);
+                    masks = latest;
+                    match start_part(&part, &masks) {
+                        Ok(started) => {
+                            child = Some(started);
+                            parts.push(part);
+                        }
+                        Err(err) => {
+                            error!("Could not start the next segment, {err}");
+                            session.request_stop();
+                        }
+                    }
+                }
+
+                if let Some(previous) = previous {
+                    stop_part(previous, &session);
+                }
+                concat_outputs(&previous_parts, &previous_filename, &suffixes);
+                segment = current;
+                session.set_video_segment(segment);
+                info!("Capture moved to {filename}");
+            }
+
+            // Until ffmpeg is done with the part, it writes the previous masks into frames it is
+            // still capturing, so they are blacked out right away.
+            let latest = tracker.current();
+            if latest != masks {
+                masks = latest;
+                if let Some(mut child) = child.take() {
+                    info!("The privacy masks changed, restarting capture via ffmpeg");
+                    hide_part(&mut child);
+                    stop_part(child, &session);
+                }
+            }
+
+            if session.is_paused() {
+                if let Some(child) = child.take() {
+                    info!("Pausing capture via ffmpeg");
+                    stop_part(child, &session);
+                }
+            } else if child.is_none() {
+                // Stopping the previous part takes a while, the windows may have moved meanwhile.
+                masks = tracker.current();
+                let part = part_filenames(&filename, &suffixes, parts.len());
+                match start_part(&part, &masks) {
+                    Ok(started) => {
+                        info!("Resuming capture via ffmpeg");
+                        child = Some(started);
+                        parts.push(part);
+                    }
+                    Err(err) => {
+                        error!("Could not resume ffmpeg capture, {err}");
+                        session.request_stop();
+                    }
+                }
+            }
+
+            if let (Some(clicks), Some(child)) = (clicks.as_mut(), child.as_mut()) {
+                clicks.follow(child);
+            }
+
+            thread::sleep(CHECK_INTERVAL);
+        }
+
+        concat_outputs(&parts, &filename, &suffixes);
+        session.request_stop();
+        session.set_video_running(false);
+    });
+
+    Ok(())
+}
+
+fn output_filename(filename: &str, suffix: &str) -> String {
+    format!("{}{suffix}.mp4", filename.trim_end_matches(".mp4"))
+}
+
+fn part_filenames(filename: &str, suffixes: &[String], index: usize) -> Vec<String> {
+    suffixes
+        .iter()
+        .map(|suffix| format!("{}{suffix}.part{index}.mp4", filename.trim_end_matches(".mp4")))
+        .collect()
+}
+
+fn concat_outputs(parts: &[Vec<String>], filename: &str, suffixes: &[String]) {
+    for (output, suffix) in suffixes.iter().enumerate() {
+        let output_parts: Vec<String> = parts.iter().map(|part| part[output].clone()).collect();
+        if let Err(err) = ffmpeg::concat_parts(&output_parts, &output_filename(filename, suffix)) {
+            error!("Could not concatenate the capture parts, {err}");
+        }
+    }
+}
+
+// Enables `HIDE_TARGET` in the part's filters.
+fn hide_part(child: &mut Child) {
+    if let Err(err) = send_command(child, HIDE_TARGET, "enable 1") {
+        error!("Could not black out ffmpeg's capture, {err}");
+    }
+}
+
+// Sends a command to the filters named `target` in every output, ffmpeg reads it after a `c`.
+pub fn send_command(child: &mut Child, target: &str, command: &str) -> Result<(), anyhow::Error> {
+    let stdin = child
+        .stdin
+        .as_mut()
+        .ok_or(Error::msg("stdin is not present"))?;
+    stdin.write_all(format!("c{target} -1 {command}\n").as_bytes())?;
+    stdin.flush()?;
+
+    Ok(())
+}
+
+// Asks ffmpeg to finish the part, killing it if the session gets terminated meanwhile.
+fn stop_part(mut child: Child, session: &Session) {
+    match child.stdin.as_mut() {
+        Some(stdin) => {
+            info!("Writing q");
+            let _ = stdin.write_all("q".as_bytes());
+            let _ = stdin.flush();
+        }
+        None => error!("Could not stop ffmpeg, stdin is not present"),
+    };
+
+    while let Ok(None) = child.try_wait() {
+        if session.is_terminated() {
+            warn!("Killing ffmpeg");
+            let _ = child.kill();
+            let _ = child.wait();
+            break;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+}
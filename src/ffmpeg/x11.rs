@@ -0,0 +1,292 @@
+use anyhow::Error;
+use log::warn;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::{
+    process::{Command, Stdio},
+    sync::Arc,
+};
+
+use crate::{
+    capture::{
+        displays::{self, DisplayInfo, Region},
+        masks::MaskRect,
+        mouse::MouseTracker,
+        Capabilities, CaptureBackend,
+    },
+    config::CaptureConfig,
+    ffmpeg::{
+        clicks::{click_filter, ClickBoxes, OutputArea},
+        drawtext::watermark_filter,
+        masks::mask_filter,
+        parts::capture_in_parts,
+    },
+    session::Session,
+};
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+struct X11Settings {
+    executable: String,
+    // The X11 display to capture, e.g. ":99" for an Xvfb display.
+    display: String,
+    // How much of the input ffmpeg reads to detect its format, e.g. "10M".
+    probesize: Option<String>,
+}
+
+impl Default for X11Settings {
+    fn default() -> Self {
+        X11Settings {
+            executable: "ffmpeg".to_string(),
+            display: ":0".to_string(),
+            probesize: None,
+        }
+    }
+}
+
+// Captures the monitors of an X11 display with ffmpeg's x11grab.
+struct X11Backend {
+    settings: X11Settings,
+}
+
+pub fn backend(settings: Map<String, Value>) -> Result<Arc<dyn CaptureBackend>, anyhow::Error> {
+    Ok(Arc::new(X11Backend {
+        settings: serde_json::from_value(Value::Object(settings))?,
+    }))
+}
+
+impl CaptureBackend for X11Backend {
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            pause: true,
+            rotation: true,
+            frame_stats: false,
+            window: false,
+            click_highlight: true,
+            window_masks: false,
+        }
+    }
+
+    fn start(
+        &self,
+        session: Arc<Session>,
+        filename: String,
+        capture_config: CaptureConfig,
+    ) -> Result<(), anyhow::Error> {
+        let settings = self.settings.clone();
+        let fps = capture_config.fps.to_string();
+        let bitrate = capture_config.bitrate.to_string();
+        let mut options = vec![
+            "-draw_mouse".to_string(),
+            (capture_config.cursor as u8).to_string(),
+            "-show_region".to_string(),
+            (capture_config.border as u8).to_string(),
+        ];
+        if let Some(probesize) = &settings.probesize {
+            options.extend(["-probesize".to_string(), probesize.clone()]);
+        }
+        let regions = match self.displays() {
+            Ok(monitors) => {
+                let selected = displays::select(monitors, capture_config.display.as_ref())?;
+                displays::regions(&selected, capture_config.layout)
+                    .iter()
+                    .map(|region| region.crop(capture_config.region.as_ref()))
+                    .collect::<Result<Vec<_>, _>>()?
+            }
+            // Without RandR, e.g. on a bare Xvfb, the whole screen is the only display. Its size
+            // is unknown then, so it can not be cropped or scaled.
+            Err(err)
+                if capture_config.display.is_none()
+                    && capture_config.region.is_none()
+                    && capture_config.output.is_none() =>
+            {
+                warn!("Capturing the whole X11 screen, {err}");
+                vec![]
+            }
+            Err(err) => return Err(err),
+        };
+        let scales: Vec<Option<String>> = regions
+            .iter()
+            .map(|region| {
+                let (width, height) = displays::scaled_size(
+                    region.width,
+                    region.height,
+                    capture_config.output,
+                    capture_config.scale,
+                );
+                ((width, height) != (region.width, region.height))
+                    .then(|| format!("scale={width}:{height}"))
+            })
+            .collect();
+        let suffixes = match regions.is_empty() {
+            true => vec![String::new()],
+            false => regions.iter().map(|region| region.suffix.clone()).collect(),
+        };
+        // The whole screen starts at the origin, its size is unknown.
+        let outputs: Vec<OutputArea> = match regions.is_empty() {
+            true => vec![OutputArea {
+                origin: (0, 0),
+                size: None,
+            }],
+            false => regions
+                .iter()
+                .map(|region| OutputArea {
+                    origin: (region.x, region.y),
+                    size: Some((region.width, region.height)),
+                })
+                .collect(),
+        };
+        let click_filters: Vec<Option<String>> = (0..outputs.len())
+            .map(|output| {
+                capture_config
+                    .click_highlight
+                    .map(|highlight| click_filter(&highlight, output))
+            })
+            .collect();
+        let clicks = match capture_config.click_highlight {
+            Some(highlight) => {
+                let mouse =
+                    MouseTracker::x11(session.clone(), &settings.display, highlight.cursor)?;
+                Some(ClickBoxes::new(highlight, mouse, outputs))
+            }
+            None => None,
+        };
+        let watermark = capture_config.watermark;
+        let session_id = session.id.clone();
+
+        let start_part = move |parts: &[String], masks: &[MaskRect]| {
+            // Built per part, the watermark's time of day depends on the day it starts.
+            let watermark = watermark
+                .as_ref()
+                .map(|watermark| watermark_filter(watermark, &session_id));
+            let mut command = Command::new(&settings.executable);
+            command.args(["-loglevel", "warning"]);
+            if regions.is_empty() {
+                command
+                    .args(["-f", "x11grab", "-framerate", &fps])
+                    .args(&options)
+                    .args(["-i", &settings.display]);
+            }
+            for Region {
+                x,
+                y,
+                width,
+                height,
+                ..
+            } in &regions
+            {
+                command
+                    .args(["-f", "x11grab", "-framerate", &fps])
+                    .args(&options)
+                    .args(["-video_size", &format!("{width}x{height}")])
+                    .args(["-i", &format!("{}+{x},{y}", settings.display)]);
+            }
+            for (input, part) in parts.iter().enumerate() {
+                command.args(["-map", &input.to_string()]);
+                // The whole screen starts at the origin, its size is unknown.
+                let masks = match regions.get(input) {
+                    Some(region) => mask_filter(
+                        masks,
+                        (region.x, region.y),
+                        Some((region.width, region.height)),
+                    ),
+                    None => mask_filter(masks, (0, 0), None),
+                };
+                let scale = scales.get(input).cloned().flatten();
+                // The clicks before the masks, so a blacked out part hides them too.
+                let filters: Vec<String> = click_filters[input]
+                    .clone()
+                    .into_iter()
+                    .chain(std::iter::once(masks))
+                    .chain(scale)
+                    .chain(watermark.clone())
+                    .collect();
+                command.args(["-vf", &filters.join(",")]);
+                command.args(["-b:v", &bitrate, "-pix_fmt", "yuv420p", "-y", part]);
+            }
+            command
+                .env("DISPLAY", &settings.display)
+                .stdin(Stdio::piped())
+                .spawn()
+                .or(Err(Error::msg("Could not start the x11grab capture")))
+        };
+
+        capture_in_parts(session, filename, suffixes, clicks, start_part)
+    }
+
+    // The RandR monitors, as listed by `xrandr --listmonitors`.
+    fn displays(&self) -> Result<Vec<DisplayInfo>, anyhow::Error> {
+        let output = Command::new("xrandr")
+            .arg("--listmonitors")
+            .env("DISPLAY", &self.settings.display)
+            .output()
+            .or(Err(Error::msg("Could not run xrandr")))?;
+        if !output.status.success() {
+            return Err(Error::msg(format!(
+                "xrandr failed on {}",
+                self.settings.display
+            )));
+        }
+
+        // Lines look like ` 0: +*DP-1 1920/527x1080/296+0+0  DP-1`, the `*` marks the primary.
+        let monitors: Vec<DisplayInfo> = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .skip(1)
+            .filter_map(parse_monitor)
+            .collect();
+        if monitors.is_empty() {
+            return Err(Error::msg("xrandr listed no monitors"));
+        }
+
+        Ok(monitors)
+    }
+}
+
+fn parse_monitor(line: &str) -> Option<DisplayInfo> {
+    let mut fields = line.split_whitespace();
+    let index = fields.next()?.trim_end_matches(':').parse().ok()?;
+    let flags = fields.next()?;
+    let (width, rest) = fields.next()?.split_once('x')?;
+    let name = fields.next()?.to_string();
+    let mut position = rest.split('+');
+    let height = position.next()?;
+
+    Some(DisplayInfo {
+        index,
+        description: None,
+        name,
+        x: position.next()?.parse().ok()?,
+        y: position.next()?.parse().ok()?,
+        width: width.split('/').next()?.parse().ok()?,
+        height: height.split('/').next()?.parse().ok()?,
+        primary: flags.contains('*'),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_the_monitors_xrandr_lists() {
+        let primary = parse_monitor(" 0: +*DP-1 1920/527x1080/296+0+0  DP-1").unwrap();
+        assert_eq!(primary.index, 0);
+        assert_eq!(primary.name, "DP-1");
+        assert_eq!((primary.x, primary.y), (0, 0));
+        assert_eq!((primary.width, primary.height), (1920, 1080));
+        assert!(primary.primary);
+
+        let secondary = parse_monitor(" 1: +HDMI-1 2560/597x1440/336+1920+120  HDMI-1").unwrap();
+        assert_eq!(secondary.index, 1);
+        assert_eq!((secondary.x, secondary.y), (1920, 120));
+        assert_eq!((secondary.width, secondary.height), (2560, 1440));
+        assert!(!secondary.primary);
+    }
+
+    #[test]
+    fn skips_what_is_not_a_monitor() {
+        assert!(parse_monitor("Monitors: 2").is_none());
+        assert!(parse_monitor("").is_none());
+        assert!(parse_monitor(" 0: +*DP-1 1920x1080  DP-1").is_none());
+    }
+}
@@ -0,0 +1,262 @@
+#[cfg(not(windows))]
+use anyhow::Error;
+use chrono::Local;
+use log::{info, warn};
+#[cfg(not(windows))]
+use std::process::Command;
+use std::{
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    api::AppState,
+    capture::window::WindowMatcher,
+    events::EventKind,
+    session::{PauseMarker, Session, SessionState},
+};
+
+// The window that has the focus.
+struct Focused {
+    title: String,
+    // Empty when it can not be told, the title may still match.
+    process: String,
+}
+
+// A pause the watcher made, which it is the only one to undo.
+struct FocusPause {
+    session: Arc<Session>,
+    // When the session was paused, None when only its video is hidden.
+    paused_at: Option<Instant>,
+}
+
+impl FocusPause {
+    // Whether the session is still in this pause, a client may have resumed it meanwhile and
+    // something else may have paused it again since.
+    fn holds(&self) -> bool {
+        match self.paused_at {
+            Some(paused_at) => self.session.paused_since() == Some(paused_at),
+            None => self.session.is_video_hidden(),
+        }
+    }
+}
+
+// Pauses the current session while the focused window matches the deny-list, see
+// `FocusWatchConfig`. Finding the focused window runs xprop or calls into Windows, so it is a
+// thread rather than a task.
+pub fn focus_task(state: Arc<AppState>) {
+    let Some(config) = state.config.focus_watch.clone() else {
+        return;
+    };
+    let deny: Vec<WindowMatcher> = config
+        .deny
+        .iter()
+        .map(|pattern| WindowMatcher::new(pattern.title.as_deref(), pattern.process.as_deref()))
+        .collect::<Result<_, _>>()
+        .expect("The focus watch deny-list is validated with the config");
+
+    thread::spawn(move || {
+        let interval = Duration::from_millis(config.poll_interval_in_ms);
+        // The pause the watcher made, it only ever undoes that one.
+        let mut paused: Option<FocusPause> = None;
+        let mut failing = false;
+
+        loop {
+            thread::sleep(interval);
+            let current = state
+                .sessions
+                .current()
+                .filter(|session| session.is_capturing());
+            let ended = |previous: &mut FocusPause| {
+                !previous.holds()
+                    || current
+                        .as_ref()
+                        .is_none_or(|session| session.id != previous.session.id)
+            };
+            // The session stopped or was resumed while paused, its marker ends there. A denied
+            // window that still has the focus pauses it again below.
+            if let Some(previous) = paused.take_if(ended) {
+                previous.session.end_marker();
+                previous.session.set_video_hidden(false);
+            }
+            let Some(session) = current else {
+                continue;
+            };
+
+            // Whatever was decided last holds until the focus is known again.
+            let focused = match focused_window(&config.display) {
+                Ok(focused) => {
+                    failing = false;
+                    focused
+                }
+                Err(err) => {
+                    if !failing {
+                        warn!("Could not tell which window has the focus, {err}");
+                        failing = true;
+                    }
+                    continue;
+                }
+            };
+            let denied = focused.and_then(|focused| {
+                deny.iter()
+                    .position(|matcher| matcher.matches(&focused.title, &focused.process))
+                    .map(|rule| (rule, focused.process))
+            });
+
+            match (denied, paused.take()) {
+                (Some((rule, process)), None) => {
+                    paused = pause(session, rule, process, config.pause_audio);
+                }
+                (None, Some(previous)) => resume(previous),
+                (_, previous) => paused = previous,
+            }
+        }
+    });
+}
+
+// Returns None when the session could not be paused, e.g. because a client already paused it.
+fn pause(session: Arc<Session>, rule: usize, process: String, audio: bool) -> Option<FocusPause> {
+    let paused_at = match audio {
+        true => {
+            session.transition(SessionState::Paused).ok()?;
+            session.emit(EventKind::SessionPaused);
+            session.paused_since()
+        }
+        false => {
+            session.set_video_hidden(true);
+            None
+        }
+    };
+    session.add_marker(PauseMarker {
+        started_at: Local::now(),
+        ended_at: None,
+        rule,
+        process: process.clone(),
+        audio,
+    });
+    session.emit(EventKind::FocusPaused {
+        rule,
+        process,
+        audio,
+    });
+    info!(
+        "Session {} paused, a window of the deny-list has the focus",
+        session.id
+    );
+
+    Some(FocusPause { session, paused_at })
+}
+
+fn resume(pause: FocusPause) {
+    let session = &pause.session;
+    match pause.paused_at {
+        None => session.set_video_hidden(false),
+        Some(_) => {
+            if pause.holds() && session.transition(SessionState::Recording).is_ok() {
+                session.emit(EventKind::SessionResumed);
+            }
+        }
+    }
+    session.end_marker();
+    session.emit(EventKind::FocusResumed);
+    info!(
+        "Session {} resumed, the focus left the deny-list",
+        session.id
+    );
+}
+
+// The window the window manager of the X11 display says is active.
+#[cfg(not(windows))]
+fn focused_window(display: &str) -> Result<Option<Focused>, anyhow::Error> {
+    // `_NET_ACTIVE_WINDOW: window id # 0x3a00007`, the id is 0x0 while nothing has the focus.
+    let root = xprop(display, &["-root", "_NET_ACTIVE_WINDOW"])?;
+    let Some(id) = root
+        .split_whitespace()
+        .last()
+        .filter(|id| id.starts_with("0x") && *id != "0x0")
+    else {
+        return Ok(None);
+    };
+
+    let mut title = None;
+    let mut pid = None;
+    for line in xprop(
+        display,
+        &["-id", id, "_NET_WM_NAME", "WM_NAME", "_NET_WM_PID"],
+    )?
+    .lines()
+    {
+        match line.split_once(" = ") {
+            Some(("_NET_WM_NAME", value)) => title = Some(unquote(value)),
+            Some(("WM_NAME", value)) if title.is_none() => title = Some(unquote(value)),
+            Some(("_NET_WM_PID", value)) => pid = value.trim().parse::<u32>().ok(),
+            _ => (),
+        }
+    }
+    // Only known for the clients on this machine that tell their pid.
+    let process = pid
+        .and_then(|pid| std::fs::read_to_string(format!("/proc/{pid}/comm")).ok())
+        .map(|name| name.trim().to_string())
+        .unwrap_or_default();
+
+    Ok(Some(Focused {
+        title: title.unwrap_or_default(),
+        process,
+    }))
+}
+
+#[cfg(not(windows))]
+fn xprop(display: &str, args: &[&str]) -> Result<String, anyhow::Error> {
+    let output = Command::new("xprop")
+        .arg("-notype")
+        .args(args)
+        .env("DISPLAY", display)
+        .output()
+        .or(Err(Error::msg("Could not run xprop")))?;
+    if !output.status.success() {
+        return Err(Error::msg(format!("xprop failed on {display}")));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+// xprop quotes the strings, escaping their quotes and backslashes.
+#[cfg(not(windows))]
+fn unquote(value: &str) -> String {
+    let value = value.trim();
+    let value = value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .unwrap_or(value);
+    let mut unquoted = String::with_capacity(value.len());
+    let mut characters = value.chars();
+    while let Some(character) = characters.next() {
+        match character {
+            '\\' => unquoted.extend(characters.next()),
+            _ => unquoted.push(character),
+        }
+    }
+    unquoted
+}
+
+#[cfg(all(windows, any(feature = "native", feature = "ffmpeg")))]
+fn focused_window(_display: &str) -> Result<Option<Focused>, anyhow::Error> {
+    // There is none while e.g. the secure desktop is up.
+    let Ok(window) = windows_capture::window::Window::foreground() else {
+        return Ok(None);
+    };
+
+    Ok(Some(Focused {
+        title: window.title().unwrap_or_default(),
+        // Windows of processes the recorder may not inspect have no process name.
+        process: window.process_name().unwrap_or_default(),
+    }))
+}
+
+#[cfg(all(windows, not(any(feature = "native", feature = "ffmpeg"))))]
+fn focused_window(_display: &str) -> Result<Option<Focused>, anyhow::Error> {
+    Err(anyhow::Error::msg(
+        "the focus is only watched with the native or ffmpeg backend built",
+    ))
+}
@@ -0,0 +1,411 @@
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::Error;
+use chrono::{DateTime, Local};
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+use crate::{
+    config::FinalizationConfig,
+    events::EventKind,
+    ffmpeg, recordings,
+    session::{Session, SessionState},
+};
+
+// How long the capture threads get to exit once they were told to terminate.
+const TERMINATION_GRACE: Duration = Duration::from_secs(5);
+
+// The finished jobs the api can still tell about, the oldest ones are forgotten first.
+const KEPT_JOBS: usize = 100;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStage {
+    // Waiting for the jobs before it to finish.
+    Queued,
+    // Waiting for the capture sources of the session to drain.
+    WaitingForCapture,
+    // Combining the video and audio outputs.
+    Combining,
+    Completed,
+    Failed,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct JobInfo {
+    pub id: String,
+    pub session_id: String,
+    pub segment: usize,
+    pub stage: JobStage,
+    pub error: Option<String>,
+    pub outputs: Vec<String>,
+    pub created_at: DateTime<Local>,
+    pub finished_at: Option<DateTime<Local>>,
+}
+
+// Finalizes a stopped session, everything that happens after the capture is done goes here.
+// A rotated segment gets a job of its own, while the session keeps recording.
+#[derive(Debug)]
+pub struct Job {
+    pub session: Arc<Session>,
+    pub segment: usize,
+    // Whether this is the session's last segment, which completes the session.
+    pub last: bool,
+    info: Mutex<JobInfo>,
+}
+
+impl Job {
+    pub fn info(&self) -> JobInfo {
+        self.info.lock().unwrap().clone()
+    }
+
+    fn set_stage(&self, stage: JobStage) {
+        self.info.lock().unwrap().stage = stage;
+    }
+
+    fn finish(&self, result: Result<Vec<String>, anyhow::Error>) {
+        let mut info = self.info.lock().unwrap();
+        match result {
+            Ok(outputs) => {
+                info.stage = JobStage::Completed;
+                info.outputs = outputs;
+            }
+            Err(err) => {
+                info.stage = JobStage::Failed;
+                info.error = Some(err.to_string());
+            }
+        }
+        info.finished_at = Some(Local::now());
+    }
+}
+
+// Runs the finalization jobs one at a time, in the order the sessions were stopped.
+pub struct JobQueue {
+    jobs: Mutex<HashMap<String, Arc<Job>>>,
+    sender: mpsc::UnboundedSender<Arc<Job>>,
+}
+
+impl JobQueue {
+    pub fn start(config: FinalizationConfig) -> Arc<Self> {
+        let (sender, mut receiver) = mpsc::unbounded_channel::<Arc<Job>>();
+
+        tokio::spawn(async move {
+            while let Some(job) = receiver.recv().await {
+                let result = run(&job, &config).await;
+                if let Err(err) = &result {
+                    error!(
+                        "Finalization of session {} segment {} failed, {err}",
+                        job.session.id, job.segment
+                    );
+                    if job.last {
+                        job.session.fail(err.to_string());
+                    }
+                }
+                job.finish(result);
+            }
+        });
+
+        Arc::new(JobQueue {
+            jobs: Mutex::new(HashMap::new()),
+            sender,
+        })
+    }
+
+    // Queues the finalization of a stopped session, or returns its job if it was already queued.
+    pub fn finalize(&self, session: Arc<Session>) -> Arc<Job> {
+        let segment = session.segment();
+        self.queue(session, segment, true)
+    }
+
+    // Queues the finalization of a segment the session rotated away from.
+    pub fn finalize_segment(&self, session: Arc<Session>, segment: usize) -> Arc<Job> {
+        self.queue(session, segment, false)
+    }
+
+    fn queue(&self, session: Arc<Session>, segment: usize, last: bool) -> Arc<Job> {
+        let mut jobs = self.jobs.lock().unwrap();
+        if let Some(job) = jobs
+            .values()
+            .find(|job| job.session.id == session.id && job.segment == segment)
+        {
+            return job.clone();
+        }
+
+        let job = Arc::new(Job {
+            info: Mutex::new(JobInfo {
+                id: Uuid::new_v4().to_string(),
+                session_id: session.id.clone(),
+                segment,
+                stage: JobStage::Queued,
+                error: None,
+                outputs: vec![],
+                created_at: Local::now(),
+                finished_at: None,
+            }),
+            session,
+            segment,
+            last,
+        });
+        jobs.insert(job.info().id, job.clone());
+        forget_oldest(&mut jobs);
+        // The worker only stops with the runtime.
+        let _ = self.sender.send(job.clone());
+
+        job
+    }
+
+    pub fn get(&self, id: &str) -> Option<Arc<Job>> {
+        self.jobs.lock().unwrap().get(id).cloned()
+    }
+}
+
+// Keeps the finished jobs under `KEPT_JOBS`, the queued and running ones are always kept.
+fn forget_oldest(jobs: &mut HashMap<String, Arc<Job>>) {
+    let mut finished: Vec<(DateTime<Local>, String)> = jobs
+        .values()
+        .map(|job| job.info())
+        .filter_map(|info| Some((info.finished_at?, info.id)))
+        .collect();
+    if finished.len() <= KEPT_JOBS {
+        return;
+    }
+    finished.sort();
+    for (_, id) in &finished[..finished.len() - KEPT_JOBS] {
+        jobs.remove(id);
+    }
+}
+
+async fn run(job: &Job, config: &FinalizationConfig) -> Result<Vec<String>, anyhow::Error> {
+    let session = &job.session;
+
+    job.set_stage(JobStage::WaitingForCapture);
+    if job.last {
+        wait_for_capture(session, config).await?;
+        session.transition(SessionState::Finalizing)?;
+    } else {
+        wait_for_segment(session, job.segment, config).await?;
+    }
+
+    job.set_stage(JobStage::Combining);
+    let filename = session.segment_filename(job.segment);
+    let suffixes = session.video_suffixes();
+    let combine_started = Instant::now();
+    let combined =
+        tokio::task::spawn_blocking(move || ffmpeg::combine_outputs(&filename, &suffixes)).await?;
+    session.metrics().observe_combine(combine_started.elapsed());
+
+    match combined {
+        Ok(outputs) => {
+            for output in &outputs {
+                session.emit(EventKind::CombineFinished {
+                    output: output.clone(),
+                });
+                if let Err(err) = recordings::mark_finalized(Path::new(output)) {
+                    error!("Could not mark {output} as finalized, {err}");
+                }
+                session.add_segment_output(output.clone());
+            }
+            if !job.last {
+                info!("Segment {} of session {} is finalized", job.segment, session.id);
+                return Ok(outputs);
+            }
+            // The first display's video, the other displays' are among the segment outputs.
+            session.complete(outputs[0].clone())?;
+            info!("Session {} is finalized", session.id);
+
+            Ok(session.segment_outputs())
+        }
+        Err(err) => {
+            session.metrics().combine_failures.increment();
+            session.emit(EventKind::CombineFailed {
+                error: err.to_string(),
+            });
+
+            Err(err)
+        }
+    }
+}
+
+// Waits for the capture sources to move past a rotated segment. They keep running, so they are
+// not terminated when it takes too long.
+async fn wait_for_segment(
+    session: &Session,
+    segment: usize,
+    config: &FinalizationConfig,
+) -> Result<(), anyhow::Error> {
+    let timeout = Duration::from_secs(config.capture_stop_timeout_in_secs);
+    let started = Instant::now();
+
+    while !session.segment_released(segment) {
+        if started.elapsed() >= timeout {
+            return Err(Error::msg(format!(
+                "The capture did not move past segment {segment} in {} seconds",
+                config.capture_stop_timeout_in_secs
+            )));
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+
+    Ok(())
+}
+
+// Waits for the capture threads to exit, terminating them once the timeout is reached.
+async fn wait_for_capture(
+    session: &Session,
+    config: &FinalizationConfig,
+) -> Result<(), anyhow::Error> {
+    let timeout = Duration::from_secs(config.capture_stop_timeout_in_secs);
+    let started = Instant::now();
+    let mut terminated_at = None;
+
+    while session.sources_running() {
+        match terminated_at {
+            None if started.elapsed() >= timeout => {
+                warn!(
+                    "Capture of session {} did not stop in {} seconds, terminating it",
+                    session.id, config.capture_stop_timeout_in_secs
+                );
+                session.terminate();
+                terminated_at = Some(Instant::now());
+            }
+            Some(terminated_at) if terminated_at.elapsed() >= TERMINATION_GRACE => {
+                session.set_video_running(false);
+                session.set_audio_running(false);
+                return Err(Error::msg(
+                    "The capture did not stop after being terminated, its outputs were abandoned",
+                ));
+            }
+            _ => (),
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::events::EventBus;
+
+    fn session() -> Arc<Session> {
+        session_in("./recordings", EventBus::new())
+    }
+
+    fn session_in(folder: &str, events: EventBus) -> Arc<Session> {
+        let settings = serde_json::from_value(json!({
+            "capture": { "backend": "native", "bitrate": 5000000, "fps": 15 },
+            "video": true,
+            "audio": true,
+            "tag": null,
+            "metadata": {},
+            "keep_alive_timeout_in_secs": 100,
+            "limits": {}
+        }))
+        .unwrap();
+        Arc::new(Session::new(folder, settings, events, Arc::default()))
+    }
+
+    #[tokio::test]
+    async fn queues_a_session_once() {
+        let queue = JobQueue::start(FinalizationConfig::default());
+        let (first, second) = (session(), session());
+
+        let job = queue.finalize(first.clone());
+        assert!(Arc::ptr_eq(&job, &queue.finalize(first)));
+        assert!(Arc::ptr_eq(&job, &queue.get(&job.info().id).unwrap()));
+        assert!(!Arc::ptr_eq(&job, &queue.finalize(second)));
+    }
+
+    #[tokio::test]
+    async fn terminates_a_capture_that_does_not_stop() {
+        let config = FinalizationConfig {
+            capture_stop_timeout_in_secs: 0,
+        };
+        let session = session();
+        session.set_video_running(true);
+
+        let capture = session.clone();
+        let thread = thread::spawn(move || {
+            while !capture.is_terminated() {
+                thread::sleep(Duration::from_millis(10));
+            }
+            capture.set_video_running(false);
+        });
+
+        wait_for_capture(&session, &config).await.unwrap();
+        assert!(session.is_terminated());
+        thread.join().unwrap();
+    }
+
+    #[tokio::test]
+    async fn waits_for_a_capture_that_stops_in_time() {
+        let session = session();
+        session.set_audio_running(true);
+
+        let capture = session.clone();
+        let thread = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            capture.set_audio_running(false);
+        });
+
+        wait_for_capture(&session, &FinalizationConfig::default())
+            .await
+            .unwrap();
+        assert!(!session.is_terminated());
+        thread.join().unwrap();
+    }
+
+    #[cfg(any(all(windows, any(feature = "native", feature = "ffmpeg")), feature = "x11"))]
+    #[tokio::test]
+    async fn finalizes_the_video_of_every_separate_display() {
+        let folder = std::env::temp_dir().join(Uuid::new_v4().to_string());
+        std::fs::create_dir_all(&folder).unwrap();
+        let events = EventBus::new();
+        let mut received = events.subscribe();
+        let session = session_in(folder.to_str().unwrap(), events);
+        session.set_video_suffixes(vec![String::new(), "-display1".to_string()]);
+        // Without an audio output there is nothing to combine, the videos are the outputs.
+        let videos = [
+            format!("{}.mp4", session.filename),
+            format!("{}-display1.mp4", session.filename),
+        ];
+        for video in &videos {
+            std::fs::write(video, "").unwrap();
+        }
+        session.transition(SessionState::Starting).unwrap();
+        session.transition(SessionState::Recording).unwrap();
+        session.request_stop();
+
+        let job = JobQueue::start(FinalizationConfig::default()).finalize(session.clone());
+        while job.info().finished_at.is_none() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        assert_eq!(job.info().outputs, videos);
+        assert_eq!(session.state(), SessionState::Completed);
+        assert_eq!(session.segment_outputs(), videos);
+        let recordings = recordings::list(folder.to_str().unwrap()).unwrap();
+        assert_eq!(recordings.len(), 2);
+        assert!(recordings.iter().all(|recording| recording.finalized));
+        let mut finished = vec![];
+        while let Ok(event) = received.try_recv() {
+            if let EventKind::CombineFinished { output } = event.kind {
+                finished.push(output);
+            }
+        }
+        assert_eq!(finished, videos);
+        std::fs::remove_dir_all(folder).unwrap();
+    }
+}
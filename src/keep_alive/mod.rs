@@ -1,26 +1,379 @@
 use std::{
-    sync::{Arc},
-    thread,
-    time::{Duration, SystemTime, UNIX_EPOCH},
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
 };
 
-use log::info;
+use chrono::{DateTime, Local, TimeDelta, Utc};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
-use crate::api::AppState;
+use crate::{
+    api::AppState,
+    config::ExpiryAction,
+    events::EventKind,
+    session::{Session, SessionState},
+};
 
-pub fn keep_alive_task(state: Arc<AppState>) {
-    thread::spawn(move || loop {
-        let recording = *state.recording.lock().unwrap();
-        let last_keep_alive = *state.last_keep_alive.lock().unwrap();
-        thread::sleep(Duration::from_secs(1));
-        let time_since_keep_alive = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
+// A client's claim on a session, the session keeps recording while any lease is unexpired.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Lease {
+    pub id: String,
+    // The authenticated client name, or the client's address when auth is off.
+    pub holder: String,
+    pub ttl_in_secs: u64,
+    pub expires_at: DateTime<Local>,
+}
+
+impl Lease {
+    fn renew(&mut self) {
+        // The ttl is bounded when the session starts, a lease that would outlast the calendar
+        // never expires.
+        self.expires_at = i64::try_from(self.ttl_in_secs)
+            .ok()
+            .and_then(TimeDelta::try_seconds)
+            .and_then(|ttl| Local::now().checked_add_signed(ttl))
+            .unwrap_or(DateTime::<Utc>::MAX_UTC.into());
+    }
+}
+
+// Where a session is in the keep-alive policy, reset whenever a lease is renewed in time.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    #[default]
+    Alive,
+    Warned,
+    Acted,
+}
+
+// The leases of a single session.
+#[derive(Debug, Default)]
+pub struct Leases {
+    leases: Mutex<HashMap<String, Lease>>,
+    phase: Mutex<Phase>,
+}
+
+impl Leases {
+    pub fn grant(&self, holder: String, ttl_in_secs: u64) -> Lease {
+        let mut lease = Lease {
+            id: Uuid::new_v4().to_string(),
+            holder,
+            ttl_in_secs,
+            expires_at: Local::now(),
+        };
+        lease.renew();
+        self.leases
+            .lock()
+            .unwrap()
+            .insert(lease.id.clone(), lease.clone());
+
+        lease
+    }
+
+    pub fn get(&self, id: &str) -> Option<Lease> {
+        self.leases.lock().unwrap().get(id).cloned()
+    }
+
+    pub fn renew(&self, id: &str) -> Option<Lease> {
+        let mut leases = self.leases.lock().unwrap();
+        let lease = leases.get_mut(id)?;
+        lease.renew();
+
+        Some(lease.clone())
+    }
+
+    // Renews every lease of the holder, for clients that do not track their lease ids.
+    pub fn renew_held_by(&self, holder: &str) -> Vec<Lease> {
+        let mut leases = self.leases.lock().unwrap();
+        leases
+            .values_mut()
+            .filter(|lease| lease.holder == holder)
+            .map(|lease| {
+                lease.renew();
+                lease.clone()
+            })
+            .collect()
+    }
+
+    // Every lease, including the expired ones, soonest to expire first.
+    pub fn list(&self) -> Vec<Lease> {
+        let mut leases: Vec<Lease> = self.leases.lock().unwrap().values().cloned().collect();
+        leases.sort_by_key(|lease| lease.expires_at);
+        leases
+    }
+
+    // When the last lease expires, or None if the session has no leases.
+    pub fn last_expiry(&self) -> Option<DateTime<Local>> {
+        self.leases
+            .lock()
             .unwrap()
-            .as_secs()
-            - last_keep_alive;
-        if recording && time_since_keep_alive >= state.config.keep_alive_timeout_in_secs {
-            *state.recording.lock().unwrap() = false;
-            info!("Closing screen capture due to lack of keep-alives!");
+            .values()
+            .map(|lease| lease.expires_at)
+            .max()
+    }
+}
+
+// Applies the keep-alive policy to the current session once every one of its leases has expired,
+// warning a little before it does.
+pub fn keep_alive_task(state: Arc<AppState>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(1));
+        loop {
+            interval.tick().await;
+            if let Some(session) = state.sessions.current() {
+                if session.is_capturing() {
+                    enforce_policy(&state, &session);
+                }
+            }
         }
     });
 }
+
+fn enforce_policy(state: &AppState, session: &Session) {
+    // The start request grants the first lease right after the session begins.
+    let Some(last_expiry) = session.leases.last_expiry() else {
+        return;
+    };
+    let policy = state.config.keep_alive_policy;
+    let grace_in_secs = match policy.action {
+        ExpiryAction::StopAfterGrace => policy.grace_in_secs,
+        _ => 0,
+    };
+    let deadline = i64::try_from(grace_in_secs)
+        .ok()
+        .and_then(TimeDelta::try_seconds)
+        .and_then(|grace| last_expiry.checked_add_signed(grace));
+    // Past the end of the calendar, it never acts.
+    let acts_in_secs =
+        deadline.map_or(i64::MAX, |deadline| (deadline - Local::now()).num_seconds());
+    let mut phase = session.leases.phase.lock().unwrap();
+
+    // The policy paused it and a client resumed it, it is paused again while the leases lack.
+    if *phase == Phase::Acted
+        && policy.action == ExpiryAction::Pause
+        && session.state() == SessionState::Recording
+    {
+        *phase = Phase::Alive;
+    }
+
+    if acts_in_secs > policy.warning_in_secs as i64 {
+        if *phase != Phase::Alive && session.is_orphaned() {
+            session.set_orphaned(false);
+            session.emit(EventKind::SessionAdopted);
+            info!("Session {} is no longer orphaned", session.id);
+        }
+        *phase = Phase::Alive;
+        return;
+    }
+
+    match *phase {
+        Phase::Alive if acts_in_secs > 0 => {
+            session.emit(EventKind::KeepAliveWarning {
+                action: policy.action,
+                acts_in_secs: acts_in_secs as u64,
+            });
+            warn!(
+                "Session {} has no keep-alives, {:?} in {acts_in_secs} seconds",
+                session.id, policy.action
+            );
+            *phase = Phase::Warned;
+        }
+        Phase::Alive | Phase::Warned if acts_in_secs <= 0 => {
+            expire(state, session, policy.action);
+            *phase = Phase::Acted;
+        }
+        _ => (),
+    }
+}
+
+fn expire(state: &AppState, session: &Session, action: ExpiryAction) {
+    let acted = match action {
+        ExpiryAction::Stop | ExpiryAction::StopAfterGrace => session.request_stop(),
+        ExpiryAction::Pause => session.transition(SessionState::Paused).is_ok(),
+        ExpiryAction::MarkOrphaned => {
+            session.set_orphaned(true);
+            true
+        }
+    };
+    // Already stopping or paused, e.g. by a client.
+    if !acted {
+        return;
+    }
+    session.emit(EventKind::KeepAliveExpired { action });
+    state.metrics.keep_alive_timeouts.increment();
+
+    match action {
+        ExpiryAction::Stop | ExpiryAction::StopAfterGrace => {
+            info!(
+                "Closing screen capture of session {} due to lack of keep-alives!",
+                session.id
+            );
+        }
+        ExpiryAction::Pause => {
+            session.emit(EventKind::SessionPaused);
+            info!(
+                "Session {} paused due to lack of keep-alives, waiting for a resume",
+                session.id
+            );
+        }
+        ExpiryAction::MarkOrphaned => {
+            warn!(
+                "Session {} is orphaned, it keeps recording until it is stopped",
+                session.id
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::{
+        capture::Backends, config::Config, events::EventBus, jobs::JobQueue, metrics::Metrics,
+        session::Registry,
+    };
+
+    fn state(policy: Value) -> AppState {
+        let config: Config = serde_json::from_value(json!({
+            "recordings_folder": "./recordings",
+            "capture": { "backend": "native", "bitrate": 5000000, "fps": 15 },
+            "keep_alive_timeout_in_secs": 100,
+            "keep_alive_policy": policy,
+            "backends": {}
+        }))
+        .unwrap();
+        let events = EventBus::new();
+        let metrics = Arc::new(Metrics::default());
+        AppState {
+            sessions: Registry::new(events.clone(), metrics.clone()),
+            backends: Backends::from_config(&config).unwrap(),
+            events,
+            jobs: JobQueue::start(config.finalization.clone()),
+            metrics,
+            config,
+        }
+    }
+
+    // A recording session whose only lease has just expired.
+    fn expired_session(state: &AppState) -> Session {
+        let settings = serde_json::from_value(json!({
+            "capture": state.config.capture,
+            "video": true,
+            "audio": true,
+            "tag": null,
+            "metadata": {},
+            "keep_alive_timeout_in_secs": 100,
+            "limits": {}
+        }))
+        .unwrap();
+        let session = Session::new(
+            &state.config.recordings_folder,
+            settings,
+            state.events.clone(),
+            state.metrics.clone(),
+        );
+        session.transition(SessionState::Starting).unwrap();
+        session.transition(SessionState::Recording).unwrap();
+        session.leases.grant("client".to_string(), 0);
+        session
+    }
+
+    fn ids(leases: &[Lease]) -> Vec<&str> {
+        leases.iter().map(|lease| lease.id.as_str()).collect()
+    }
+
+    #[test]
+    fn grants_leases_that_expire_after_their_ttl() {
+        let leases = Leases::default();
+        let lease = leases.grant("client".to_string(), 60);
+        assert!(lease.expires_at > Local::now() + TimeDelta::seconds(59));
+        assert_eq!(leases.get(&lease.id).unwrap().holder, "client");
+    }
+
+    #[test]
+    fn tracks_when_the_last_lease_expires() {
+        let leases = Leases::default();
+        assert_eq!(leases.last_expiry(), None);
+        let longer = leases.grant("first".to_string(), 60);
+        leases.grant("second".to_string(), 0);
+        assert_eq!(leases.last_expiry(), Some(longer.expires_at));
+    }
+
+    #[test]
+    fn renews_by_id_or_by_holder() {
+        let leases = Leases::default();
+        let a = leases.grant("a".to_string(), 60);
+        let b = leases.grant("b".to_string(), 60);
+        let longer = leases.grant("a".to_string(), 120);
+
+        let renewed = leases.renew(&a.id).unwrap();
+        assert!(renewed.expires_at > a.expires_at);
+        assert!(leases.renew("missing").is_none());
+        assert_eq!(ids(&leases.list()), [&b.id, &a.id, &longer.id]);
+
+        let mut held = leases.renew_held_by("a");
+        held.sort_by_key(|lease| lease.ttl_in_secs);
+        assert_eq!(ids(&held), [&a.id, &longer.id]);
+        assert!(leases.renew_held_by("c").is_empty());
+    }
+
+    #[tokio::test]
+    async fn stops_an_expired_session() {
+        for action in [ExpiryAction::Stop, ExpiryAction::StopAfterGrace] {
+            let state = state(json!({}));
+            let session = expired_session(&state);
+            expire(&state, &session, action);
+            assert_eq!(session.state(), SessionState::Stopping);
+            assert_eq!(state.metrics.keep_alive_timeouts.get(), 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn pauses_an_expired_session() {
+        let state = state(json!({}));
+        let session = expired_session(&state);
+        expire(&state, &session, ExpiryAction::Pause);
+        assert_eq!(session.state(), SessionState::Paused);
+    }
+
+    #[tokio::test]
+    async fn keeps_recording_an_orphaned_session() {
+        let state = state(json!({ "action": "mark_orphaned" }));
+        let session = expired_session(&state);
+        enforce_policy(&state, &session);
+        assert_eq!(session.state(), SessionState::Recording);
+        assert!(session.is_orphaned());
+
+        session.leases.grant("client".to_string(), 60);
+        enforce_policy(&state, &session);
+        assert!(!session.is_orphaned());
+    }
+
+    #[tokio::test]
+    async fn warns_before_the_grace_ends() {
+        let state = state(json!({
+            "action": "stop_after_grace",
+            "grace_in_secs": 5,
+            "warning_in_secs": 10
+        }));
+        let session = expired_session(&state);
+        enforce_policy(&state, &session);
+        assert_eq!(session.state(), SessionState::Recording);
+        assert_eq!(*session.leases.phase.lock().unwrap(), Phase::Warned);
+        assert_eq!(state.metrics.keep_alive_timeouts.get(), 0);
+    }
+
+    #[tokio::test]
+    async fn acts_once_without_a_grace() {
+        let state = state(json!({ "action": "stop" }));
+        let session = expired_session(&state);
+        enforce_policy(&state, &session);
+        enforce_policy(&state, &session);
+        assert_eq!(session.state(), SessionState::Stopping);
+        assert_eq!(*session.leases.phase.lock().unwrap(), Phase::Acted);
+        assert_eq!(state.metrics.keep_alive_timeouts.get(), 1);
+    }
+}
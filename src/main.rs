@@ -1,18 +1,26 @@
 mod api;
 mod capture;
 mod config;
+mod events;
+mod focus;
+mod jobs;
 mod keep_alive;
 mod logger;
+mod metrics;
 mod audio;
 mod ffmpeg;
+#[cfg(all(windows, feature = "native"))]
 mod native_capture;
-
-use log::info;
+mod recordings;
+mod session;
+#[cfg(feature = "synthetic")]
+mod synthetic;
+mod uploader;
+mod webhooks;
 
 #[tokio::main]
 async fn main() {
     logger::init_logger();
-    info!("The Rust-Recorder is listening on port {}", 3030);
     let config = config::get_config().expect("./config.json should be present and valid");
     std::fs::create_dir_all(&config.recordings_folder).expect("Could not create the recordings folder");
     api::start(config).await;
@@ -0,0 +1,151 @@
+use std::{
+    fmt::Write,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+#[derive(Debug, Default)]
+pub struct Counter(AtomicU64);
+
+impl Counter {
+    pub fn increment(&self) {
+        self.add(1);
+    }
+
+    pub fn add(&self, value: u64) {
+        self.0.fetch_add(value, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+// The recorder's counters, updated by the api, the capture threads and the finalization jobs.
+// Served in the Prometheus text format by GET /metrics.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    pub recordings_started: Counter,
+    pub recordings_completed: Counter,
+    pub recordings_failed: Counter,
+    pub keep_alive_timeouts: Counter,
+    pub frames_received: Counter,
+    pub frames_encoded: Counter,
+    pub audio_samples_written: Counter,
+    pub audio_samples_dropped: Counter,
+    pub combine_failures: Counter,
+    // The total time spent combining, and how many combines it covers.
+    combine_duration: Mutex<(Duration, u64)>,
+}
+
+// The values that are only known when scraping.
+pub struct Gauges {
+    pub session_duration: Duration,
+    pub free_disk_space: Option<u64>,
+}
+
+impl Metrics {
+    pub fn observe_combine(&self, duration: Duration) {
+        let mut combine_duration = self.combine_duration.lock().unwrap();
+        combine_duration.0 += duration;
+        combine_duration.1 += 1;
+    }
+
+    pub fn render(&self, gauges: &Gauges) -> String {
+        let mut output = String::new();
+        let mut counter = |name: &str, help: &str, counter: &Counter| {
+            write_metric(&mut output, name, help, "counter", counter.get());
+        };
+
+        counter(
+            "recorder_recordings_started_total",
+            "Recordings that were started.",
+            &self.recordings_started,
+        );
+        counter(
+            "recorder_recordings_completed_total",
+            "Recordings that were finalized successfully.",
+            &self.recordings_completed,
+        );
+        counter(
+            "recorder_recordings_failed_total",
+            "Recordings that failed.",
+            &self.recordings_failed,
+        );
+        counter(
+            "recorder_keep_alive_timeouts_total",
+            "Recordings that were stopped for lack of keep-alives.",
+            &self.keep_alive_timeouts,
+        );
+        counter(
+            "recorder_frames_received_total",
+            "Frames received from the native capture, including the ones dropped while paused.",
+            &self.frames_received,
+        );
+        counter(
+            "recorder_frames_encoded_total",
+            "Frames sent to the native video encoder.",
+            &self.frames_encoded,
+        );
+        counter(
+            "recorder_audio_samples_written_total",
+            "Audio samples written to the wav outputs.",
+            &self.audio_samples_written,
+        );
+        counter(
+            "recorder_audio_samples_dropped_total",
+            "Audio samples lost because the writer was busy or failed.",
+            &self.audio_samples_dropped,
+        );
+        counter(
+            "recorder_combine_failures_total",
+            "Failed combines of the video and audio outputs.",
+            &self.combine_failures,
+        );
+
+        let (combine_duration, combines) = *self.combine_duration.lock().unwrap();
+        let _ = writeln!(
+            output,
+            "# HELP recorder_combine_duration_seconds Time spent combining the video and audio outputs.\n\
+             # TYPE recorder_combine_duration_seconds summary\n\
+             recorder_combine_duration_seconds_sum {}\n\
+             recorder_combine_duration_seconds_count {combines}",
+            combine_duration.as_secs_f64()
+        );
+
+        write_metric(
+            &mut output,
+            "recorder_session_duration_seconds",
+            "How long the current recording has been running, 0 when nothing is recording.",
+            "gauge",
+            gauges.session_duration.as_secs_f64(),
+        );
+        if let Some(free_disk_space) = gauges.free_disk_space {
+            write_metric(
+                &mut output,
+                "recorder_recordings_folder_free_bytes",
+                "Free disk space available to the recordings folder.",
+                "gauge",
+                free_disk_space,
+            );
+        }
+
+        output
+    }
+}
+
+fn write_metric(
+    output: &mut String,
+    name: &str,
+    help: &str,
+    kind: &str,
+    value: impl std::fmt::Display,
+) {
+    let _ = writeln!(
+        output,
+        "# HELP {name} {help}\n# TYPE {name} {kind}\n{name} {value}"
+    );
+}
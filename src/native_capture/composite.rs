@@ -0,0 +1,224 @@
+use log::{error, info};
+use std::{
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use windows_capture::{
+    capture::{Context, GraphicsCaptureApiHandler},
+    frame::Frame,
+    graphics_capture_api::InternalCaptureControl,
+    monitor::Monitor,
+};
+
+use super::{
+    capture_settings, create_encoder, render, to_bgra, transform::Transform, VideoRunning,
+};
+use crate::{
+    capture::{
+        clicks::ClickOverlay,
+        displays::{self, DisplayInfo},
+        masks::MaskTracker,
+        mouse::MouseTracker,
+        watermark::WatermarkOverlay,
+    },
+    config::{CaptureConfig, DisplayLayout},
+    events::EventKind,
+    session::Session,
+};
+
+// The composited frame as wide as the displays' bounding box.
+struct Canvas {
+    buffer: Vec<u8>,
+    width: usize,
+    height: usize,
+}
+
+struct DisplayFlags {
+    canvas: Arc<Mutex<Canvas>>,
+    // Where the display is on the canvas.
+    left: usize,
+    top: usize,
+    session: Arc<Session>,
+}
+
+// Draws a display's frames into its place on the canvas.
+struct DisplayCapture {
+    flags: DisplayFlags,
+}
+
+impl GraphicsCaptureApiHandler for DisplayCapture {
+    type Flags = DisplayFlags;
+
+    type Error = anyhow::Error;
+
+    fn new(ctx: Context<Self::Flags>) -> Result<Self, Self::Error> {
+        Ok(Self { flags: ctx.flags })
+    }
+
+    fn on_frame_arrived(
+        &mut self,
+        frame: &mut Frame,
+        _: InternalCaptureControl,
+    ) -> Result<(), Self::Error> {
+        let DisplayFlags {
+            canvas,
+            left,
+            top,
+            session,
+        } = &self.flags;
+        session.frame_received();
+        if session.is_paused() {
+            return Ok(());
+        }
+
+        let format = frame.color_format();
+        let mut buffer = frame.buffer()?;
+        let row_length = buffer.width() as usize * 4;
+        let rows = buffer.as_nopadding_buffer()?;
+        to_bgra(rows, format);
+        let mut canvas = canvas.lock().unwrap();
+        let (width, height) = (canvas.width, canvas.height);
+        // Clipped, in case the display changed its resolution since the capture started.
+        let length = row_length.min((width - left) * 4);
+        for (row, pixels) in rows.chunks_exact(row_length).enumerate() {
+            let y = top + row;
+            if y >= height {
+                break;
+            }
+            let start = (y * width + left) * 4;
+            canvas.buffer[start..start + length].copy_from_slice(&pixels[..length]);
+        }
+
+        Ok(())
+    }
+}
+
+// Captures every display into one video. Frames only arrive when a display changes, so the canvas
+// is encoded at the capture's fps instead of on every frame.
+pub fn record_displays(
+    session: Arc<Session>,
+    filename: String,
+    capture_config: CaptureConfig,
+    monitors: Vec<(DisplayInfo, Monitor)>,
+) -> Result<(), anyhow::Error> {
+    let selected: Vec<DisplayInfo> = monitors
+        .iter()
+        .map(|(display, _)| display.clone())
+        .collect();
+    let bounds = displays::regions(&selected, DisplayLayout::Composite).remove(0);
+    let mut transform = Transform::new(
+        &bounds,
+        &bounds.crop(capture_config.region.as_ref())?,
+        &capture_config,
+    );
+    let (width, height) = (bounds.width as usize, bounds.height as usize);
+    let canvas = Arc::new(Mutex::new(Canvas {
+        buffer: vec![0; width * height * 4],
+        width,
+        height,
+    }));
+    let (output_width, output_height) = (transform.width, transform.height);
+    let origin = (bounds.x, bounds.y);
+    let clicks = capture_config
+        .click_highlight
+        .map(|highlight| ClickOverlay::new(highlight, MouseTracker::start(session.clone())));
+    let watermark = capture_config
+        .watermark
+        .as_ref()
+        .map(|watermark| WatermarkOverlay::new(watermark, &session.id));
+    let masks = MaskTracker::start(session.clone());
+    let mut encoder = create_encoder(output_width, output_height, &capture_config, &filename)?;
+    info!(
+        "Compositing {} displays into a {width}x{height} capture",
+        monitors.len()
+    );
+
+    let running = VideoRunning::start(session.clone());
+    thread::spawn(move || {
+        let captures: Vec<_> = monitors
+            .into_iter()
+            .filter_map(|(display, monitor)| {
+                let flags = DisplayFlags {
+                    canvas: canvas.clone(),
+                    left: (display.x - bounds.x) as usize,
+                    top: (display.y - bounds.y) as usize,
+                    session: session.clone(),
+                };
+                let settings = capture_settings(monitor, &capture_config, flags);
+                DisplayCapture::start_free_threaded(settings)
+                    .inspect_err(|err| error!("Could not capture {}, {err}", display.name))
+                    .ok()
+            })
+            .collect();
+
+        let frame_interval = Duration::from_secs_f64(1.0 / capture_config.fps as f64);
+        let start = Instant::now();
+        let mut segment = session.segment();
+        let mut encoded: u64 = 0;
+
+        while session.is_capturing() && !captures.iter().all(|capture| capture.is_finished()) {
+            thread::sleep(frame_interval);
+
+            let current = session.segment();
+            if current != segment {
+                let filename = format!("{}.mp4", session.segment_filename(current));
+                match create_encoder(output_width, output_height, &capture_config, &filename) {
+                    Ok(next) => {
+                        if let Err(err) = std::mem::replace(&mut encoder, next).finish() {
+                            error!("Could not finish segment {segment}, {err}");
+                        }
+                        segment = current;
+                        session.set_video_segment(segment);
+                        info!("Capture moved to {filename}");
+                    }
+                    Err(err) => {
+                        error!("Could not start the next segment, {err}");
+                        break;
+                    }
+                }
+            }
+
+            if session.is_paused() {
+                continue;
+            }
+            // Timestamps are in 100-nanosecond units, without the time spent paused.
+            let elapsed = start.elapsed().saturating_sub(session.paused_for());
+            let canvas = canvas.lock().unwrap();
+            let pixels = render(
+                &mut transform,
+                &canvas.buffer,
+                canvas.width,
+                origin,
+                &masks.current(),
+                clicks.as_ref(),
+                watermark.as_ref(),
+            );
+            if let Err(err) = encoder.send_frame_buffer(pixels, (elapsed.as_nanos() / 100) as i64) {
+                error!("Could not encode the composited frame, {err}");
+                break;
+            }
+            drop(canvas);
+            session.frame_encoded();
+            if encoded == 0 {
+                session.emit(EventKind::FirstFrameReceived);
+            }
+            encoded += 1;
+        }
+
+        session.request_stop();
+        for capture in captures {
+            if let Err(err) = capture.stop() {
+                error!("Could not stop a display capture, {err}");
+            }
+        }
+        if let Err(err) = encoder.finish() {
+            error!("Could not finish the composited capture, {err}");
+        }
+        drop(running);
+        info!("Composited capture is done, encoded {encoded} frames");
+    });
+
+    Ok(())
+}
@@ -1,9 +1,10 @@
 use anyhow::Ok;
-use log::{info, warn};
+use log::{error, info, warn};
+use serde_json::{Map, Value};
 use std::{
     io::{self, Write},
     path::Path,
-    sync::{Arc, Mutex},
+    sync::Arc,
     thread,
     time::{Duration, Instant},
 };
@@ -14,15 +15,87 @@ use windows_capture::{
     frame::Frame,
     graphics_capture_api::InternalCaptureControl,
     monitor::Monitor,
-    settings::{ColorFormat, CursorCaptureSettings, DrawBorderSettings, Settings},
+    settings::{
+        ColorFormat, CursorCaptureSettings, DirtyRegionSettings, DrawBorderSettings,
+        MinimumUpdateIntervalSettings, SecondaryWindowSettings, Settings,
+        TryIntoCaptureItemWithType,
+    },
 };
 
-use crate::config::CaptureConfig;
+use crate::{
+    capture::{
+        clicks::ClickOverlay,
+        displays::{self, DisplayInfo},
+        masks::{self, MaskRect, MaskTracker},
+        mouse::MouseTracker,
+        watermark::WatermarkOverlay,
+        Capabilities, CaptureBackend,
+    },
+    config::{CaptureConfig, DirtyRegionMode, DisplayLayout, FrameFormat},
+    events::EventKind,
+    session::Session,
+};
+
+mod composite;
+mod transform;
+mod window;
+
+use transform::Transform;
+
 // Handles capture events.
 struct Capture {
     // The video encoder that will be used to encode the frames.
     encoder: Option<VideoEncoder>,
     flags: CustomFlags,
+    received_frame: bool,
+    // The session segment the encoder writes to.
+    segment: usize,
+}
+
+impl Capture {
+    // Finishes the current video and starts the next one once the session rotated.
+    fn follow_segment(&mut self) -> Result<(), anyhow::Error> {
+        let segment = self.flags.session.segment();
+        if segment == self.segment {
+            return Ok(());
+        }
+
+        let filename = format!(
+            "{}{}.mp4",
+            self.flags.session.segment_filename(segment),
+            self.flags.suffix
+        );
+        let encoder = create_encoder(
+            self.flags.width,
+            self.flags.height,
+            &self.flags.capture_config,
+            &filename,
+        )?;
+        if let Some(previous) = self.encoder.replace(encoder) {
+            previous.finish()?;
+        }
+        self.segment = segment;
+        self.flags.session.set_video_segment(segment);
+        info!("Capture moved to {filename}");
+
+        Ok(())
+    }
+}
+
+fn create_encoder(
+    width: u32,
+    height: u32,
+    capture_config: &CaptureConfig,
+    filename: &str,
+) -> Result<VideoEncoder, anyhow::Error> {
+    Ok(VideoEncoder::new(
+        VideoSettingsBuilder::new(width, height)
+            .bitrate(capture_config.bitrate)
+            .frame_rate(capture_config.fps),
+        AudioSettingsBuilder::default().disabled(true),
+        ContainerSettingsBuilder::default(),
+        Path::new(filename),
+    )?)
 }
 
 #[derive(Debug)]
@@ -30,8 +103,16 @@ struct CustomFlags {
     width: u32,
     height: u32,
     filename: Arc<String>,
+    // Appended to the segment's name, for the other displays of a separate capture.
+    suffix: String,
+    transform: Transform,
+    // Where the display starts on the virtual screen.
+    origin: (i32, i32),
+    masks: MaskTracker,
+    clicks: Option<ClickOverlay>,
+    watermark: Option<WatermarkOverlay>,
     capture_config: CaptureConfig,
-    recording_raw: Arc<Mutex<bool>>,
+    session: Arc<Session>,
 }
 
 impl GraphicsCaptureApiHandler for Capture {
@@ -48,18 +129,19 @@ impl GraphicsCaptureApiHandler for Capture {
             ctx.flags
         );
 
-        let encoder = VideoEncoder::new(
-            VideoSettingsBuilder::new(ctx.flags.width, ctx.flags.height)
-                .bitrate(ctx.flags.capture_config.bitrate)
-                .frame_rate(ctx.flags.capture_config.fps),
-            AudioSettingsBuilder::default().disabled(true),
-            ContainerSettingsBuilder::default(),
-            Path::new(&*ctx.flags.filename.as_str()),
+        let encoder = create_encoder(
+            ctx.flags.width,
+            ctx.flags.height,
+            &ctx.flags.capture_config,
+            ctx.flags.filename.as_str(),
         )?;
+        let segment = ctx.flags.session.segment();
 
         Ok(Self {
             encoder: Some(encoder),
             flags: ctx.flags,
+            received_frame: false,
+            segment,
         })
     }
 
@@ -67,12 +149,62 @@ impl GraphicsCaptureApiHandler for Capture {
     fn on_frame_arrived(
         &mut self,
         frame: &mut Frame,
-        _: InternalCaptureControl,
+        capture_control: InternalCaptureControl,
     ) -> Result<(), Self::Error> {
-        // Send the frame to the video encoder
-        self.encoder.as_mut().unwrap().send_frame(frame)?;
+        // Finalizing gave up on the capture, nothing more goes to its output.
+        if self.flags.session.is_terminated() {
+            capture_control.stop();
+            return Ok(());
+        }
+        self.flags.session.frame_received();
+        self.follow_segment()?;
 
-        *self.flags.recording_raw.clone().lock().unwrap() = true;
+        // Drop the frames while paused, and shift the later ones back by the time spent paused
+        // so the output has no gap.
+        if self.flags.session.is_paused() {
+            return Ok(());
+        }
+        let paused_for = self.flags.session.paused_for();
+        let masks = self.flags.masks.current();
+
+        // Send the frame to the video encoder, through the cpu when it has to be changed
+        let transform = &mut self.flags.transform;
+        if paused_for.is_zero()
+            && frame.color_format() == ColorFormat::Bgra8
+            && masks.is_empty()
+            && self.flags.clicks.is_none()
+            && self.flags.watermark.is_none()
+            && transform.is_identity(frame.width(), frame.height())
+        {
+            self.encoder.as_mut().unwrap().send_frame(frame)?;
+        } else {
+            // Timestamps are in 100-nanosecond units.
+            let timestamp = frame.timestamp().Duration - (paused_for.as_nanos() / 100) as i64;
+            let format = frame.color_format();
+            let mut buffer = frame.buffer()?;
+            let width = buffer.width() as usize;
+            let source = buffer.as_nopadding_buffer()?;
+            to_bgra(source, format);
+            let pixels = render(
+                transform,
+                source,
+                width,
+                self.flags.origin,
+                &masks,
+                self.flags.clicks.as_ref(),
+                self.flags.watermark.as_ref(),
+            );
+            self.encoder
+                .as_mut()
+                .unwrap()
+                .send_frame_buffer(pixels, timestamp)?;
+        }
+        self.flags.session.frame_encoded();
+
+        if !self.received_frame {
+            self.received_frame = true;
+            self.flags.session.emit(EventKind::FirstFrameReceived);
+        }
 
         // Note: The frame has other uses too, for example, you can save a single frame to a file, like this:
         // frame.save_as_image("frame.png", ImageFormat::Png)?;
@@ -85,81 +217,267 @@ impl GraphicsCaptureApiHandler for Capture {
     // Optional handler called when the capture item (usually a window) closes.
     fn on_closed(&mut self) -> Result<(), Self::Error> {
         warn!("Capture window has been closed");
+        self.flags.session.emit(EventKind::CaptureWindowClosed);
 
         Ok(())
     }
 }
 
-pub fn record_screen(
-    recording: Arc<Mutex<bool>>,
-    recording_raw: Arc<Mutex<bool>>,
-    filename: String,
-    capture_config: CaptureConfig,
-) -> Result<(), anyhow::Error> {
-    // Gets the foreground window, refer to the docs for other capture items
-    let primary_monitor = Monitor::primary()?;
-
-    let dimensions = CustomFlags {
-        width: primary_monitor.width()?,
-        height: primary_monitor.height()?,
-        filename: Arc::new(filename),
-        capture_config,
-        recording_raw: recording_raw.clone(),
-    };
-
-    let settings = Settings::new(
+// Crops and scales a frame that starts at `origin` on the virtual screen, hides the masks in it,
+// then draws the overlays over it.
+fn render<'a>(
+    transform: &'a mut Transform,
+    source: &[u8],
+    source_width: usize,
+    origin: (i32, i32),
+    masks: &[MaskRect],
+    clicks: Option<&ClickOverlay>,
+    watermark: Option<&WatermarkOverlay>,
+) -> &'a [u8] {
+    let (width, height) = (transform.width as usize, transform.height as usize);
+    let hidden: Vec<_> = masks
+        .iter()
+        .map(|mask| (mask.within(origin), mask.style))
+        .filter(|((left, top, right, bottom), _)| left < right && top < bottom)
+        .map(|(area, style)| (transform.map_rect(area), style))
+        .collect();
+    let rings = clicks
+        .map(|clicks| clicks.rings(origin, |x, y| transform.map(x, y)))
+        .unwrap_or_default();
+    let pixels = transform.apply(source, source_width);
+    for (area, style) in hidden {
+        masks::hide(pixels, width, area, style, true);
+    }
+    if let Some(clicks) = clicks {
+        clicks.draw(pixels, (width, height), rings, true);
+    }
+    if let Some(watermark) = watermark {
+        watermark.draw(pixels, width, (0, 0, width, height), true);
+    }
+
+    pixels
+}
+
+// The transform, the overlays and the encoder take BGRA.
+fn to_bgra(pixels: &mut [u8], format: ColorFormat) {
+    if format == ColorFormat::Rgba8 {
+        for pixel in pixels.chunks_exact_mut(4) {
+            pixel.swap(0, 2);
+        }
+    }
+}
+
+// The settings every capture of a monitor or window uses.
+fn capture_settings<Flags, Item: TryIntoCaptureItemWithType>(
+    item: Item,
+    capture_config: &CaptureConfig,
+    flags: Flags,
+) -> Settings<Flags, Item> {
+    Settings::new(
         // Item to capture
-        primary_monitor,
+        item,
         // Capture cursor settings
-        CursorCaptureSettings::WithCursor,
+        match capture_config.cursor {
+            true => CursorCaptureSettings::WithCursor,
+            false => CursorCaptureSettings::WithoutCursor,
+        },
         // Draw border settings
-        DrawBorderSettings::WithoutBorder,
+        match capture_config.border {
+            true => DrawBorderSettings::WithBorder,
+            false => DrawBorderSettings::WithoutBorder,
+        },
+        SecondaryWindowSettings::Default,
+        match capture_config.min_update_interval_in_ms {
+            Some(interval) => {
+                MinimumUpdateIntervalSettings::Custom(Duration::from_millis(interval))
+            }
+            None => MinimumUpdateIntervalSettings::Default,
+        },
+        match capture_config.dirty_region {
+            DirtyRegionMode::Default => DirtyRegionSettings::Default,
+            DirtyRegionMode::ReportOnly => DirtyRegionSettings::ReportOnly,
+            DirtyRegionMode::ReportAndRender => DirtyRegionSettings::ReportAndRender,
+        },
+        match capture_config.color_format {
+            FrameFormat::Bgra8 => ColorFormat::Bgra8,
+            FrameFormat::Rgba8 => ColorFormat::Rgba8,
+        },
+        // Additional flags for the capture settings that will be passed to user defined `new` function.
+        flags,
+    )
+}
 
-        windows_capture::settings::SecondaryWindowSettings::Default,
+// Captures the selected monitors through the Windows Graphics Capture api.
+struct NativeBackend;
 
-        windows_capture::settings::MinimumUpdateIntervalSettings::Default,
+pub fn backend(settings: Map<String, Value>) -> Result<Arc<dyn CaptureBackend>, anyhow::Error> {
+    if !settings.is_empty() {
+        return Err(anyhow::Error::msg("The native backend takes no settings"));
+    }
 
-        windows_capture::settings::DirtyRegionSettings::Default,
-        // The desired color format for the captured frame.
-        ColorFormat::Rgba8,
-        // Additional flags for the capture settings that will be passed to user defined `new` function.
-        dimensions,
+    Ok(Arc::new(NativeBackend))
+}
+
+impl CaptureBackend for NativeBackend {
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            pause: true,
+            rotation: true,
+            frame_stats: true,
+            window: true,
+            click_highlight: true,
+            window_masks: true,
+        }
+    }
+
+    fn start(
+        &self,
+        session: Arc<Session>,
+        filename: String,
+        capture_config: CaptureConfig,
+    ) -> Result<(), anyhow::Error> {
+        match capture_config.window.clone() {
+            Some(target) => window::record_window(session, capture_config, target),
+            None => record_screen(session, filename, capture_config),
+        }
+    }
+
+    fn displays(&self) -> Result<Vec<DisplayInfo>, anyhow::Error> {
+        Ok(displays::monitors()?
+            .into_iter()
+            .map(|(display, _)| display)
+            .collect())
+    }
+}
+
+// Marks the session's video as running until it is dropped, even when a capture thread panics, so
+// finalizing does not wait for it forever.
+struct VideoRunning(Arc<Session>);
+
+impl VideoRunning {
+    fn start(session: Arc<Session>) -> Self {
+        session.set_video_running(true);
+        VideoRunning(session)
+    }
+}
+
+impl Drop for VideoRunning {
+    fn drop(&mut self) {
+        self.0.set_video_running(false);
+    }
+}
+
+fn record_screen(
+    session: Arc<Session>,
+    filename: String,
+    capture_config: CaptureConfig,
+) -> Result<(), anyhow::Error> {
+    let monitors = displays::monitors()?;
+    let selected = displays::select(
+        monitors.iter().map(|(display, _)| display.clone()).collect(),
+        capture_config.display.as_ref(),
+    )?;
+    let mut selected: Vec<(DisplayInfo, Monitor)> = monitors
+        .into_iter()
+        .filter(|(display, _)| selected.iter().any(|other| other.index == display.index))
+        .collect();
+    // The first selected display writes the session's video, keep the primary one first.
+    selected.sort_by_key(|(display, _)| !display.primary);
+
+    if selected.len() > 1 && capture_config.layout == DisplayLayout::Composite {
+        return composite::record_displays(session, filename, capture_config, selected);
+    }
+
+    let regions = displays::regions(
+        &selected
+            .iter()
+            .map(|(display, _)| display.clone())
+            .collect::<Vec<_>>(),
+        DisplayLayout::Separate,
     );
+    session.set_video_suffixes(regions.iter().map(|region| region.suffix.clone()).collect());
+    let clicks = capture_config
+        .click_highlight
+        .map(|highlight| ClickOverlay::new(highlight, MouseTracker::start(session.clone())));
+    let watermark = capture_config
+        .watermark
+        .as_ref()
+        .map(|watermark| WatermarkOverlay::new(watermark, &session.id));
+    let masks = MaskTracker::start(session.clone());
+    let mut settings: Vec<Settings<CustomFlags, Monitor>> = vec![];
+    for ((_, monitor), region) in selected.into_iter().zip(regions) {
+        let transform = Transform::new(
+            &region,
+            &region.crop(capture_config.region.as_ref())?,
+            &capture_config,
+        );
+        let flags = CustomFlags {
+            width: transform.width,
+            height: transform.height,
+            filename: Arc::new(format!(
+                "{}{}.mp4",
+                filename.trim_end_matches(".mp4"),
+                region.suffix
+            )),
+            suffix: region.suffix,
+            transform,
+            origin: (region.x, region.y),
+            masks: masks.clone(),
+            clicks: clicks.clone(),
+            watermark: watermark.clone(),
+            capture_config: capture_config.clone(),
+            session: session.clone(),
+        };
+        settings.push(capture_settings(monitor, &capture_config, flags));
+    }
 
-    *recording.clone().lock().unwrap() = true;
+    // Running from now on, so finalizing waits for the encoders even without a frame.
+    let running = VideoRunning::start(session.clone());
 
-    // Starts the capture and takes control of the current thread.
+    // Starts the captures, every one of them runs on a thread of its own.
     // The errors from handler trait will end up here
     thread::spawn(move || {
-        let capture = Capture::start_free_threaded(settings).ok();
+        let captures: Vec<_> = settings
+            .into_iter()
+            .filter_map(|settings| {
+                Capture::start_free_threaded(settings)
+                    .inspect_err(|err| error!("Could not start the capture, {err}"))
+                    .ok()
+            })
+            .collect();
         let start = Instant::now();
 
         loop {
-            if !*recording.lock().unwrap()
-                || (capture.is_some() && capture.as_ref().unwrap().is_finished())
-            {
+            if !session.is_capturing() || captures.iter().all(|capture| capture.is_finished()) {
                 println!();
                 break;
             }
+            // Frames only arrive when the screen changes, so a rotation may not wait for one.
+            for capture in &captures {
+                if let Err(err) = capture.callback().lock().follow_segment() {
+                    error!("Could not rotate the capture, {err}");
+                }
+            }
             print!("\rRecording for {} seconds...", start.elapsed().as_secs());
             let _ = io::stdout().flush();
             thread::sleep(Duration::from_millis(100));
         }
 
-        *recording.lock().unwrap() = false;
+        session.request_stop();
 
-        if capture.is_some() {
-            capture.unwrap().stop()?;
+        // Every display's encoder has to finish, whichever of them fails to.
+        for capture in captures {
+            if let Err(err) = capture.stop() {
+                error!("Could not stop a display capture, {err}");
+            }
         }
 
-        *recording_raw.lock().unwrap() = false;
+        drop(running);
 
         info!(
             "Capture is done, ran for {} seconds",
             start.elapsed().as_secs()
         );
-
-        Ok(())
     });
 
     Ok(())
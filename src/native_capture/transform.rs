@@ -0,0 +1,135 @@
+use crate::{
+    capture::displays::{self, Region},
+    config::CaptureConfig,
+};
+
+// Crops and scales the captured BGRA frames on the cpu, for the frames that are sent as buffers.
+// The output is bottom to top, like send_frame_buffer expects it.
+#[derive(Debug, Clone)]
+pub struct Transform {
+    // The recorded part of the frame.
+    x: usize,
+    y: usize,
+    crop_width: usize,
+    crop_height: usize,
+    pub width: u32,
+    pub height: u32,
+    // Reused between frames.
+    output: Vec<u8>,
+}
+
+impl Transform {
+    // The transform of a region, relative to the frame captured at `origin`.
+    pub fn new(origin: &Region, region: &Region, capture_config: &CaptureConfig) -> Self {
+        let (width, height) = displays::scaled_size(
+            region.width,
+            region.height,
+            capture_config.output,
+            capture_config.scale,
+        );
+        Transform {
+            x: (region.x - origin.x) as usize,
+            y: (region.y - origin.y) as usize,
+            crop_width: region.width as usize,
+            crop_height: region.height as usize,
+            width,
+            height,
+            output: vec![],
+        }
+    }
+
+    // Whether the frames can go to the encoder as they are.
+    pub fn is_identity(&self, frame_width: u32, frame_height: u32) -> bool {
+        self.x == 0
+            && self.y == 0
+            && (self.crop_width, self.crop_height) == (frame_width as usize, frame_height as usize)
+            && (self.width, self.height) == (frame_width, frame_height)
+    }
+
+    // Where a point of the source frame is in the output, and how much larger things are there.
+    pub fn map(&self, x: f32, y: f32) -> (f32, f32, f32) {
+        let scale_x = self.width as f32 / self.crop_width as f32;
+        let scale_y = self.height as f32 / self.crop_height as f32;
+        (
+            (x - self.x as f32) * scale_x,
+            (y - self.y as f32) * scale_y,
+            (scale_x + scale_y) / 2.0,
+        )
+    }
+
+    // The left, top, right and bottom of the output pixels a rectangle of the source frame shows
+    // in. A pixel more on every side, the scaling blends every pixel with its neighbours.
+    pub fn map_rect(
+        &self,
+        (left, top, right, bottom): (usize, usize, usize, usize),
+    ) -> (usize, usize, usize, usize) {
+        let (left, top, _) = self.map(left as f32, top as f32);
+        let (right, bottom, _) = self.map(right as f32, bottom as f32);
+        let clamp = |value: f32, length: u32| (value.max(0.0) as usize).min(length as usize);
+
+        (
+            clamp(left.floor() - 1.0, self.width),
+            clamp(top.floor() - 1.0, self.height),
+            clamp(right.ceil() + 1.0, self.width),
+            clamp(bottom.ceil() + 1.0, self.height),
+        )
+    }
+
+    pub fn apply(&mut self, source: &[u8], source_width: usize) -> &mut [u8] {
+        let (width, height) = (self.width as usize, self.height as usize);
+        self.output.resize(width * height * 4, 0);
+        let source_height = source.len() / (source_width * 4).max(1);
+        // The frame may have shrunk since the capture started, what is outside of it stays black.
+        let crop_width = self.crop_width.min(source_width.saturating_sub(self.x));
+        let crop_height = self.crop_height.min(source_height.saturating_sub(self.y));
+        if crop_width == 0 || crop_height == 0 {
+            self.output.fill(0);
+            return &mut self.output;
+        }
+
+        if (crop_width, crop_height) == (width, height) {
+            for (row, output) in self.output.chunks_exact_mut(width * 4).rev().enumerate() {
+                let start = ((self.y + row) * source_width + self.x) * 4;
+                output.copy_from_slice(&source[start..start + width * 4]);
+            }
+            return &mut self.output;
+        }
+
+        // Bilinear, with the weights in 1/256ths.
+        let columns: Vec<(usize, usize, u32)> = (0..width)
+            .map(|x| sample(x, width, crop_width, self.x))
+            .collect();
+        for (row, output) in self.output.chunks_exact_mut(width * 4).rev().enumerate() {
+            let (top, bottom, weight_y) = sample(row, height, crop_height, self.y);
+            let top = &source[top * source_width * 4..(top + 1) * source_width * 4];
+            let bottom = &source[bottom * source_width * 4..(bottom + 1) * source_width * 4];
+            for (pixel, &(left, right, weight_x)) in output.chunks_exact_mut(4).zip(&columns) {
+                for channel in 0..4 {
+                    let blend = |line: &[u8]| {
+                        line[left * 4 + channel] as u32 * (256 - weight_x)
+                            + line[right * 4 + channel] as u32 * weight_x
+                    };
+                    let value = (blend(top) * (256 - weight_y) + blend(bottom) * weight_y) >> 16;
+                    pixel[channel] = value as u8;
+                }
+            }
+        }
+
+        &mut self.output
+    }
+}
+
+// The two source positions an output position falls between, and the weight of the second one.
+fn sample(
+    position: usize,
+    length: usize,
+    source_length: usize,
+    offset: usize,
+) -> (usize, usize, u32) {
+    let center = ((position as f32 + 0.5) * source_length as f32 / length as f32 - 0.5).max(0.0);
+    let first = (center as usize).min(source_length - 1);
+    let second = (first + 1).min(source_length - 1);
+    let weight = ((center - first as f32) * 256.0) as u32;
+
+    (offset + first, offset + second, weight.min(256))
+}
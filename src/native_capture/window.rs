@@ -0,0 +1,303 @@
+use log::{error, info, warn};
+use std::{
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use windows_capture::{
+    capture::{CaptureControl, Context, GraphicsCaptureApiHandler},
+    encoder::VideoEncoder,
+    frame::Frame,
+    graphics_capture_api::InternalCaptureControl,
+    window::Window,
+};
+
+use super::{
+    capture_settings, create_encoder, render, to_bgra, transform::Transform, VideoRunning,
+};
+use crate::{
+    capture::{
+        clicks::ClickOverlay,
+        displays::Region,
+        masks::MaskTracker,
+        mouse::MouseTracker,
+        watermark::WatermarkOverlay,
+        window::{screen_rect, WindowMatcher},
+    },
+    config::{CaptureConfig, WindowClosedAction, WindowTarget},
+    events::EventKind,
+    session::Session,
+};
+
+// Looking for the window lists every window, so it is not done on every frame.
+const SEARCH_INTERVAL: Duration = Duration::from_millis(500);
+
+// The window's latest frame, its size follows the window's.
+#[derive(Default)]
+struct LastFrame {
+    buffer: Vec<u8>,
+    width: usize,
+    height: usize,
+    // Where the window was on the virtual screen.
+    x: i32,
+    y: i32,
+}
+
+struct WindowFlags {
+    window: Window,
+    frame: Arc<Mutex<LastFrame>>,
+    session: Arc<Session>,
+}
+
+// Keeps the window's latest frame for the encoding thread.
+struct WindowCapture {
+    flags: WindowFlags,
+}
+
+impl GraphicsCaptureApiHandler for WindowCapture {
+    type Flags = WindowFlags;
+
+    type Error = anyhow::Error;
+
+    fn new(ctx: Context<Self::Flags>) -> Result<Self, Self::Error> {
+        Ok(Self { flags: ctx.flags })
+    }
+
+    fn on_frame_arrived(
+        &mut self,
+        frame: &mut Frame,
+        _: InternalCaptureControl,
+    ) -> Result<(), Self::Error> {
+        self.flags.session.frame_received();
+        if self.flags.session.is_paused() {
+            return Ok(());
+        }
+
+        let format = frame.color_format();
+        let mut buffer = frame.buffer()?;
+        let (width, height) = (buffer.width() as usize, buffer.height() as usize);
+        let pixels = buffer.as_nopadding_buffer()?;
+        to_bgra(pixels, format);
+        let position = screen_rect(&self.flags.window).ok();
+        let mut last = self.flags.frame.lock().unwrap();
+        last.buffer.clear();
+        last.buffer.extend_from_slice(pixels);
+        last.width = width;
+        last.height = height;
+        if let Some(rect) = position {
+            (last.x, last.y) = (rect.left, rect.top);
+        }
+
+        Ok(())
+    }
+
+    // The capture ends with the window, the encoding thread decides what happens next.
+    fn on_closed(&mut self) -> Result<(), Self::Error> {
+        warn!("Capture window has been closed");
+        self.flags.session.emit(EventKind::CaptureWindowClosed);
+
+        Ok(())
+    }
+}
+
+// Captures the window the target matches, once it is open. Like the composited capture, the
+// latest frame is encoded at the capture's fps, so the video keeps the session's pace while the
+// window is away.
+pub fn record_window(
+    session: Arc<Session>,
+    capture_config: CaptureConfig,
+    target: WindowTarget,
+) -> Result<(), anyhow::Error> {
+    let matcher = WindowMatcher::new(target.title.as_deref(), target.process.as_deref())?;
+    let frame = Arc::new(Mutex::new(LastFrame::default()));
+    let clicks = capture_config
+        .click_highlight
+        .map(|highlight| ClickOverlay::new(highlight, MouseTracker::start(session.clone())));
+    let watermark = capture_config
+        .watermark
+        .as_ref()
+        .map(|watermark| WatermarkOverlay::new(watermark, &session.id));
+    let masks = MaskTracker::start(session.clone());
+
+    // Running while it waits for the window too, so finalizing waits for the encoder.
+    let running = VideoRunning::start(session.clone());
+    thread::spawn(move || {
+        let frame_interval = Duration::from_secs_f64(1.0 / capture_config.fps as f64);
+        let start = Instant::now();
+        let mut capture: Option<CaptureControl<WindowCapture, anyhow::Error>> = None;
+        let mut searched: Option<Instant> = None;
+        let mut waiting = false;
+        // Sized by the first frame, the later ones are scaled or cropped to it.
+        let mut output: Option<(VideoEncoder, Transform)> = None;
+        let mut segment = session.segment();
+        let mut encoded: u64 = 0;
+
+        while session.is_capturing() {
+            thread::sleep(frame_interval);
+
+            let current = session.segment();
+            if current != segment {
+                let filename = format!("{}.mp4", session.segment_filename(current));
+                if let Some((encoder, transform)) = &mut output {
+                    let next = create_encoder(
+                        transform.width,
+                        transform.height,
+                        &capture_config,
+                        &filename,
+                    );
+                    match next {
+                        Ok(next) => {
+                            if let Err(err) = std::mem::replace(encoder, next).finish() {
+                                error!("Could not finish segment {segment}, {err}");
+                            }
+                            info!("Capture moved to {filename}");
+                        }
+                        Err(err) => {
+                            error!("Could not start the next segment, {err}");
+                            break;
+                        }
+                    }
+                }
+                segment = current;
+                session.set_video_segment(segment);
+            }
+
+            if capture.as_ref().is_none_or(|capture| capture.is_finished()) {
+                if let Some(closed) = capture.take() {
+                    if let Err(err) = closed.stop() {
+                        error!("Could not stop the window capture, {err}");
+                    }
+                    if target.on_closed == WindowClosedAction::Stop {
+                        break;
+                    }
+                }
+                if searched.is_none_or(|searched| searched.elapsed() >= SEARCH_INTERVAL) {
+                    searched = Some(Instant::now());
+                    match matcher.find() {
+                        Ok(Some((window, title))) => {
+                            let flags = WindowFlags {
+                                window,
+                                frame: frame.clone(),
+                                session: session.clone(),
+                            };
+                            match WindowCapture::start_free_threaded(capture_settings(
+                                window,
+                                &capture_config,
+                                flags,
+                            )) {
+                                Ok(control) => {
+                                    info!("Capturing the window {title}");
+                                    session.emit(EventKind::WindowFound { title });
+                                    capture = Some(control);
+                                    waiting = false;
+                                }
+                                Err(err) => {
+                                    error!("Could not capture the window {title}, {err}");
+                                    break;
+                                }
+                            }
+                        }
+                        Ok(None) if !waiting => {
+                            info!("Waiting for the window to open");
+                            session.emit(EventKind::WaitingForWindow);
+                            waiting = true;
+                        }
+                        Ok(None) => (),
+                        Err(err) => error!("Could not look for the window, {err}"),
+                    }
+                }
+            }
+
+            if session.is_paused() {
+                continue;
+            }
+            let last = frame.lock().unwrap();
+            if last.width == 0 || last.height == 0 {
+                continue;
+            }
+            if output.is_none() {
+                match start_output(&session, &capture_config, &last, segment) {
+                    Ok(started) => output = Some(started),
+                    Err(err) => {
+                        error!("Could not start the window capture, {err}");
+                        break;
+                    }
+                }
+            }
+            let Some((encoder, transform)) = &mut output else {
+                break;
+            };
+
+            // Timestamps are in 100-nanosecond units, without the time spent paused.
+            let elapsed = start.elapsed().saturating_sub(session.paused_for());
+            let pixels = render(
+                transform,
+                &last.buffer,
+                last.width,
+                (last.x, last.y),
+                &masks.current(),
+                clicks.as_ref(),
+                watermark.as_ref(),
+            );
+            if let Err(err) = encoder.send_frame_buffer(pixels, (elapsed.as_nanos() / 100) as i64) {
+                error!("Could not encode the window's frame, {err}");
+                break;
+            }
+            drop(last);
+            session.frame_encoded();
+            if encoded == 0 {
+                session.emit(EventKind::FirstFrameReceived);
+            }
+            encoded += 1;
+        }
+
+        session.request_stop();
+        if let Some(capture) = capture {
+            if let Err(err) = capture.stop() {
+                error!("Could not stop the window capture, {err}");
+            }
+        }
+        if let Some((encoder, _)) = output {
+            if let Err(err) = encoder.finish() {
+                error!("Could not finish the window capture, {err}");
+            }
+        }
+        drop(running);
+        info!("Window capture is done, encoded {encoded} frames");
+    });
+
+    Ok(())
+}
+
+// The encoder for the window's video, sized by its first frame. The video starts black at the
+// session's start, so it stays in sync with the audio when the window opened later.
+fn start_output(
+    session: &Session,
+    capture_config: &CaptureConfig,
+    first: &LastFrame,
+    segment: usize,
+) -> Result<(VideoEncoder, Transform), anyhow::Error> {
+    let window = Region {
+        suffix: String::new(),
+        x: 0,
+        y: 0,
+        width: first.width as u32,
+        height: first.height as u32,
+    };
+    let transform = Transform::new(
+        &window,
+        &window.crop(capture_config.region.as_ref())?,
+        capture_config,
+    );
+    let filename = format!("{}.mp4", session.segment_filename(segment));
+    let mut encoder = create_encoder(transform.width, transform.height, capture_config, &filename)?;
+    let black = vec![0; transform.width as usize * transform.height as usize * 4];
+    encoder.send_frame_buffer(&black, 0)?;
+    info!(
+        "Window capture is {}x{}, into {filename}",
+        transform.width, transform.height
+    );
+
+    Ok((encoder, transform))
+}
@@ -0,0 +1,328 @@
+use std::{
+    collections::HashSet,
+    fs::{self, File},
+    io::{Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::Error;
+use chrono::{DateTime, Local};
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::events::{EventBus, EventKind};
+
+// Written next to a recording once it was uploaded, e.g. `18.10.2026-10_00_00-combined.mp4.uploaded`.
+pub const UPLOADED_MARKER_EXTENSION: &str = "uploaded";
+// Written by the finalization job next to its output, the parts and the outputs it combined have
+// none.
+const FINALIZED_MARKER_EXTENSION: &str = "finalized";
+
+const RECORDING_EXTENSIONS: [&str; 2] = ["mp4", "wav"];
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum UploadState {
+    Pending,
+    Uploaded,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Recording {
+    pub name: String,
+    pub size: u64,
+    pub duration_in_secs: Option<f64>,
+    pub created_at: Option<DateTime<Local>>,
+    pub combined: bool,
+    pub finalized: bool,
+    pub upload: UploadState,
+}
+
+// Lists the recordings in the folder, newest first.
+pub fn list(recordings_folder: &str) -> Result<Vec<Recording>, anyhow::Error> {
+    let mut recordings = vec![];
+    for entry in fs::read_dir(recordings_folder)?.flatten() {
+        let path = entry.path();
+        if !is_recording(&path) {
+            continue;
+        }
+        if let Some(name) = path.file_name().and_then(|name| name.to_str()) {
+            // A file that is being written or removed does not hide the others.
+            match inspect(recordings_folder, name) {
+                Ok(recording) => recordings.push(recording),
+                Err(err) => warn!("Skipping the recording {name}, {err}"),
+            }
+        }
+    }
+    recordings.sort_by_key(|recording| std::cmp::Reverse(recording.created_at));
+
+    Ok(recordings)
+}
+
+pub fn inspect(recordings_folder: &str, name: &str) -> Result<Recording, anyhow::Error> {
+    let path = resolve(recordings_folder, name)?;
+    let metadata = fs::metadata(&path)?;
+    let created_at = metadata
+        .created()
+        .or(metadata.modified())
+        .ok()
+        .map(DateTime::<Local>::from);
+
+    Ok(Recording {
+        name: name.to_string(),
+        size: metadata.len(),
+        duration_in_secs: duration(&path),
+        created_at,
+        combined: name.ends_with("-combined.mp4"),
+        finalized: marker(&path, FINALIZED_MARKER_EXTENSION).exists(),
+        upload: if upload_marker(&path).exists() {
+            UploadState::Uploaded
+        } else {
+            UploadState::Pending
+        },
+    })
+}
+
+// Returns the path of a recording in the folder, refusing anything that is not a plain file name.
+pub fn resolve(recordings_folder: &str, name: &str) -> Result<PathBuf, anyhow::Error> {
+    if name.is_empty()
+        || name.starts_with('.')
+        || name.contains(['/', '\\', ':'])
+        || !is_recording(Path::new(name))
+    {
+        return Err(Error::msg(format!("Invalid recording name {name}")));
+    }
+    let path = Path::new(recordings_folder).join(name);
+    if !path.is_file() {
+        return Err(Error::msg(format!("Recording {name} does not exist")));
+    }
+
+    Ok(path)
+}
+
+pub fn remove(path: &Path) -> Result<(), anyhow::Error> {
+    fs::remove_file(path)?;
+    for extension in [UPLOADED_MARKER_EXTENSION, FINALIZED_MARKER_EXTENSION] {
+        let marker = marker(path, extension);
+        if marker.exists() {
+            fs::remove_file(marker)?;
+        }
+    }
+
+    Ok(())
+}
+
+// Emits `UploadFinished` whenever an uploaded marker appears. The markers present at startup were
+// already reported.
+pub fn watch_uploads(recordings_folder: String, events: EventBus) {
+    tokio::spawn(async move {
+        let mut reported = uploaded(&recordings_folder);
+        let mut interval = tokio::time::interval(Duration::from_secs(5));
+        loop {
+            interval.tick().await;
+            let current = uploaded(&recordings_folder);
+            for recording in current.difference(&reported) {
+                events.emit(
+                    None,
+                    EventKind::UploadFinished {
+                        recording: recording.clone(),
+                    },
+                );
+            }
+            reported = current;
+        }
+    });
+}
+
+// The names of the recordings that have an uploaded marker.
+fn uploaded(recordings_folder: &str) -> HashSet<String> {
+    let suffix = format!(".{UPLOADED_MARKER_EXTENSION}");
+    fs::read_dir(recordings_folder)
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter_map(|entry| {
+                    let name = entry.file_name().to_str()?.to_string();
+                    Some(name.strip_suffix(&suffix)?.to_string())
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+pub fn upload_marker(path: &Path) -> PathBuf {
+    marker(path, UPLOADED_MARKER_EXTENSION)
+}
+
+pub fn mark_finalized(path: &Path) -> Result<(), anyhow::Error> {
+    Ok(fs::write(marker(path, FINALIZED_MARKER_EXTENSION), "")?)
+}
+
+fn marker(path: &Path, extension: &str) -> PathBuf {
+    let mut marker = path.as_os_str().to_owned();
+    marker.push(format!(".{extension}"));
+    PathBuf::from(marker)
+}
+
+pub fn content_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("mp4") => "video/mp4",
+        Some("wav") => "audio/wav",
+        _ => "application/octet-stream",
+    }
+}
+
+fn is_recording(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| RECORDING_EXTENSIONS.contains(&extension))
+}
+
+fn duration(path: &Path) -> Option<f64> {
+    match path.extension()?.to_str()? {
+        "mp4" => mp4_duration(path).ok().flatten(),
+        "wav" => {
+            let reader = hound::WavReader::open(path).ok()?;
+            Some(reader.duration() as f64 / reader.spec().sample_rate as f64)
+        }
+        _ => None,
+    }
+}
+
+// Reads the duration from the `moov/mvhd` box, which is written once the mp4 is finalized.
+fn mp4_duration(path: &Path) -> Result<Option<f64>, anyhow::Error> {
+    let mut file = File::open(path)?;
+    let length = file.metadata()?.len();
+
+    let Some((moov_start, moov_end)) = find_box(&mut file, 0, length, b"moov")? else {
+        return Ok(None);
+    };
+    let Some((mvhd_start, _)) = find_box(&mut file, moov_start, moov_end, b"mvhd")? else {
+        return Ok(None);
+    };
+
+    file.seek(SeekFrom::Start(mvhd_start))?;
+    let mut version = [0u8; 4];
+    file.read_exact(&mut version)?;
+    let (timescale, duration) = if version[0] == 1 {
+        let mut fields = [0u8; 28];
+        file.read_exact(&mut fields)?;
+        (
+            u32::from_be_bytes(fields[16..20].try_into()?),
+            u64::from_be_bytes(fields[20..28].try_into()?),
+        )
+    } else {
+        let mut fields = [0u8; 16];
+        file.read_exact(&mut fields)?;
+        (
+            u32::from_be_bytes(fields[8..12].try_into()?),
+            u32::from_be_bytes(fields[12..16].try_into()?) as u64,
+        )
+    };
+
+    if timescale == 0 {
+        return Ok(None);
+    }
+
+    Ok(Some(duration as f64 / timescale as f64))
+}
+
+// Scans the boxes between `start` and `end`, returning the payload range of the first `kind` box.
+fn find_box(
+    file: &mut File,
+    start: u64,
+    end: u64,
+    kind: &[u8; 4],
+) -> Result<Option<(u64, u64)>, anyhow::Error> {
+    let mut offset = start;
+    while end.saturating_sub(offset) >= 8 {
+        file.seek(SeekFrom::Start(offset))?;
+        let mut header = [0u8; 8];
+        file.read_exact(&mut header)?;
+        let mut size = u32::from_be_bytes(header[0..4].try_into()?) as u64;
+        let mut header_size = 8;
+        if size == 1 {
+            let mut large_size = [0u8; 8];
+            file.read_exact(&mut large_size)?;
+            size = u64::from_be_bytes(large_size);
+            header_size = 16;
+        } else if size == 0 {
+            size = end - offset;
+        }
+        // A size past what a file can hold is as malformed as one shorter than its header.
+        let Some(box_end) = offset.checked_add(size).filter(|_| size >= header_size) else {
+            return Ok(None);
+        };
+        if &header[4..8] == kind {
+            return Ok(Some((offset + header_size, box_end.min(end))));
+        }
+        offset = box_end;
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mp4_box(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+        let mut bytes = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+        bytes.extend_from_slice(kind);
+        bytes.extend_from_slice(payload);
+        bytes
+    }
+
+    // The duration of an mp4 made of the boxes, written to a file of its own.
+    fn duration_of(boxes: &[Vec<u8>]) -> Option<f64> {
+        let path = std::env::temp_dir().join(format!("{}.mp4", uuid::Uuid::new_v4()));
+        fs::write(&path, boxes.concat()).unwrap();
+        let duration = mp4_duration(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        duration
+    }
+
+    fn mvhd_v0(timescale: u32, duration: u32) -> Vec<u8> {
+        let mut payload = vec![0u8; 12];
+        payload.extend_from_slice(&timescale.to_be_bytes());
+        payload.extend_from_slice(&duration.to_be_bytes());
+        payload.extend_from_slice(&[0u8; 80]);
+        mp4_box(b"mvhd", &payload)
+    }
+
+    #[test]
+    fn reads_the_duration_from_mvhd() {
+        let ftyp = mp4_box(b"ftyp", b"isom\0\0\x02\0isomiso2");
+        let moov = mp4_box(b"moov", &mvhd_v0(1000, 2500));
+        assert_eq!(duration_of(&[ftyp, moov]), Some(2.5));
+    }
+
+    #[test]
+    fn gives_up_on_a_box_size_past_the_largest_offset() {
+        let ftyp = mp4_box(b"ftyp", b"isom\0\0\x02\0isomiso2");
+        let mut free = 1u32.to_be_bytes().to_vec();
+        free.extend_from_slice(b"free");
+        free.extend_from_slice(&u64::MAX.to_be_bytes());
+        let moov = mp4_box(b"moov", &mvhd_v0(1000, 2500));
+        assert_eq!(duration_of(&[ftyp, free, moov]), None);
+    }
+
+    #[test]
+    fn reads_the_64_bit_duration_of_version_1() {
+        let mut payload = vec![1u8, 0, 0, 0];
+        payload.extend_from_slice(&[0u8; 16]);
+        payload.extend_from_slice(&90000u32.to_be_bytes());
+        payload.extend_from_slice(&(90000u64 * 3).to_be_bytes());
+        let moov = mp4_box(b"moov", &mp4_box(b"mvhd", &payload));
+        assert_eq!(duration_of(&[moov]), Some(3.0));
+    }
+
+    #[test]
+    fn has_no_duration_until_finalized() {
+        // What a capture writes before the moov box, e.g. while it is recording.
+        let mdat = mp4_box(b"mdat", &[0u8; 64]);
+        assert_eq!(duration_of(&[mdat]), None);
+        assert_eq!(duration_of(&[mp4_box(b"moov", &mvhd_v0(0, 10))]), None);
+    }
+}
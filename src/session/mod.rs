@@ -0,0 +1,736 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::Error;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    config::PrivacyMask,
+    events::{EventBus, EventKind},
+    keep_alive::{Lease, Leases},
+    metrics::{Counter, Metrics},
+    session::options::SessionSettings,
+};
+
+pub mod options;
+
+// The finished sessions the api can still tell about, the oldest ones are forgotten first.
+const KEPT_SESSIONS: usize = 100;
+
+// The lifecycle of a recording, from the recorder's point of view.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionState {
+    // Nothing is being recorded, a new recording may start.
+    Idle,
+    // A start request is setting up the capture sources.
+    Starting,
+    // The capture sources are running.
+    Recording,
+    // The capture sources are running but drop everything they capture.
+    Paused,
+    // A stop was requested, waiting for the capture sources to drain.
+    Stopping,
+    // The capture sources are done, the outputs are being combined.
+    Finalizing,
+    // The recording is done and its output is on disk.
+    Completed,
+    // The recording failed, see the session error.
+    Failed,
+}
+
+impl SessionState {
+    pub fn can_transition_to(self, next: SessionState) -> bool {
+        use SessionState::*;
+        matches!(
+            (self, next),
+            (Idle, Starting)
+                | (Starting, Recording | Stopping | Failed)
+                | (Recording, Paused | Stopping)
+                | (Paused, Recording | Stopping)
+                | (Stopping, Finalizing | Failed)
+                | (Finalizing, Completed | Failed)
+        )
+    }
+
+    // Whether the capture sources should keep running.
+    pub fn is_capturing(self) -> bool {
+        matches!(
+            self,
+            SessionState::Starting | SessionState::Recording | SessionState::Paused
+        )
+    }
+
+    // Whether the session still holds the capture sources, so a new one may not start.
+    pub fn is_busy(self) -> bool {
+        self.is_capturing() || self == SessionState::Stopping
+    }
+
+    // Whether the session is done with, nothing will touch its outputs anymore.
+    pub fn is_terminal(self) -> bool {
+        matches!(
+            self,
+            SessionState::Idle | SessionState::Completed | SessionState::Failed
+        )
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct SessionOutputs {
+    pub video: String,
+    pub audio: String,
+    // The final file, known once the session is completed.
+    pub output: Option<String>,
+    // The combined file of every finalized segment, in order, see `Session::rotate`.
+    pub segments: Vec<String>,
+}
+
+// A part of the recording the focus watcher paused, see `FocusWatchConfig`.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct PauseMarker {
+    pub started_at: DateTime<Local>,
+    // Unset while it is still paused.
+    pub ended_at: Option<DateTime<Local>>,
+    // The index of the deny-list entry the focused window matched. Its title is left out, it may
+    // be sensitive itself.
+    pub rule: usize,
+    pub process: String,
+    // Whether the audio was paused too, otherwise only the video was blacked out.
+    pub audio: bool,
+}
+
+// A snapshot of a session, as returned by the api.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct SessionInfo {
+    pub id: String,
+    pub state: SessionState,
+    pub error: Option<String>,
+    pub started_at: DateTime<Local>,
+    pub stopped_at: Option<DateTime<Local>>,
+    pub paused_for_in_secs: u64,
+    pub leases: Vec<Lease>,
+    // Every lease has expired and the keep-alive policy kept it recording anyway.
+    pub orphaned: bool,
+    pub outputs: SessionOutputs,
+    pub settings: SessionSettings,
+    // The masks the capture hides now, the settings hold the ones it started with.
+    pub privacy_masks: Vec<PrivacyMask>,
+    // Next to the metadata the client started it with.
+    pub markers: Vec<PauseMarker>,
+}
+
+// A single recording, shared between the api and the capture threads.
+// Every state change goes through `transition`.
+#[derive(Debug)]
+pub struct Session {
+    pub id: String,
+    // The outputs path without an extension, e.g. `./recordings/18.10.2026-10_00_00-1b4e28ba`.
+    // The start of the id keeps the sessions started in the same second apart.
+    pub filename: String,
+    pub started_at: DateTime<Local>,
+    pub settings: SessionSettings,
+    pub leases: Leases,
+    state: Mutex<SessionState>,
+    error: Mutex<Option<String>>,
+    stopped_at: Mutex<Option<DateTime<Local>>>,
+    output: Mutex<Option<String>>,
+    paused_at: Mutex<Option<Instant>>,
+    paused_for: Mutex<Duration>,
+    video_running: Mutex<bool>,
+    audio_running: Mutex<bool>,
+    terminated: Mutex<bool>,
+    orphaned: Mutex<bool>,
+    // The segment the outputs are currently written to.
+    segment: Mutex<usize>,
+    // When the current segment started, and the time spent paused before it.
+    segment_started: Mutex<(Instant, Duration)>,
+    // The segment every source is currently writing.
+    video_segment: Mutex<usize>,
+    audio_segment: Mutex<usize>,
+    segment_outputs: Mutex<Vec<String>>,
+    // Appended to the segment's filename for each of its videos, one per display with a separate
+    // layout. The first one is empty.
+    video_suffixes: Mutex<Vec<String>>,
+    privacy_masks: Mutex<Vec<PrivacyMask>>,
+    // Whether the video is blacked out, while the audio goes on.
+    video_hidden: Mutex<bool>,
+    markers: Mutex<Vec<PauseMarker>>,
+    frames_received: Counter,
+    frames_encoded: Counter,
+    events: EventBus,
+    metrics: Arc<Metrics>,
+}
+
+impl Session {
+    pub fn new(
+        recordings_folder: &str,
+        settings: SessionSettings,
+        events: EventBus,
+        metrics: Arc<Metrics>,
+    ) -> Self {
+        let id = Uuid::new_v4().to_string();
+        let started_at = Local::now();
+        let mut filename = format!(
+            "{}/{}-{}",
+            recordings_folder,
+            started_at.format("%d.%m.%Y-%H_%M_%S"),
+            &id[..8]
+        );
+        if let Some(tag) = &settings.tag {
+            filename = format!("{filename}-{tag}");
+        }
+        Session {
+            id,
+            filename,
+            started_at,
+            privacy_masks: Mutex::new(settings.capture.privacy_masks.clone()),
+            settings,
+            leases: Leases::default(),
+            state: Mutex::new(SessionState::Idle),
+            error: Mutex::new(None),
+            stopped_at: Mutex::new(None),
+            output: Mutex::new(None),
+            paused_at: Mutex::new(None),
+            paused_for: Mutex::new(Duration::ZERO),
+            video_running: Mutex::new(false),
+            audio_running: Mutex::new(false),
+            terminated: Mutex::new(false),
+            orphaned: Mutex::new(false),
+            segment: Mutex::new(0),
+            segment_started: Mutex::new((Instant::now(), Duration::ZERO)),
+            video_segment: Mutex::new(0),
+            audio_segment: Mutex::new(0),
+            segment_outputs: Mutex::new(vec![]),
+            video_suffixes: Mutex::new(vec![String::new()]),
+            video_hidden: Mutex::new(false),
+            markers: Mutex::new(vec![]),
+            frames_received: Counter::default(),
+            frames_encoded: Counter::default(),
+            events,
+            metrics,
+        }
+    }
+
+    // Whether the file is one of this session's outputs, including intermediate ones.
+    pub fn owns(&self, path: &std::path::Path) -> bool {
+        let stem = std::path::Path::new(&self.filename).file_name();
+        match (
+            stem.and_then(|stem| stem.to_str()),
+            path.file_name().and_then(|name| name.to_str()),
+        ) {
+            (Some(stem), Some(name)) => name.starts_with(stem),
+            _ => false,
+        }
+    }
+
+    // The outputs path of a segment without an extension, the first segment has no suffix.
+    pub fn segment_filename(&self, segment: usize) -> String {
+        match segment {
+            0 => self.filename.clone(),
+            _ => format!("{}-seg{segment}", self.filename),
+        }
+    }
+
+    // The video output of the current segment.
+    pub fn video_filename(&self) -> String {
+        format!("{}.mp4", self.segment_filename(self.segment()))
+    }
+
+    // The audio output of the current segment.
+    pub fn audio_filename(&self) -> String {
+        format!("{}.wav", self.segment_filename(self.segment()))
+    }
+
+    pub fn state(&self) -> SessionState {
+        *self.state.lock().unwrap()
+    }
+
+    pub fn error(&self) -> Option<String> {
+        self.error.lock().unwrap().clone()
+    }
+
+    pub fn info(&self) -> SessionInfo {
+        SessionInfo {
+            id: self.id.clone(),
+            state: self.state(),
+            error: self.error(),
+            started_at: self.started_at,
+            stopped_at: *self.stopped_at.lock().unwrap(),
+            paused_for_in_secs: self.paused_for().as_secs(),
+            leases: self.leases.list(),
+            orphaned: self.is_orphaned(),
+            outputs: SessionOutputs {
+                video: self.video_filename(),
+                audio: self.audio_filename(),
+                output: self.output.lock().unwrap().clone(),
+                segments: self.segment_outputs.lock().unwrap().clone(),
+            },
+            settings: self.settings.clone(),
+            privacy_masks: self.privacy_masks(),
+            markers: self.markers.lock().unwrap().clone(),
+        }
+    }
+
+    // Atomically moves to `next`, failing if it is not reachable from the current state.
+    pub fn transition(&self, next: SessionState) -> Result<(), anyhow::Error> {
+        let mut state = self.state.lock().unwrap();
+        if !state.can_transition_to(next) {
+            return Err(Error::msg(format!(
+                "Invalid session transition from {:?} to {:?}",
+                *state, next
+            )));
+        }
+        if *state == SessionState::Paused {
+            if let Some(paused_at) = self.paused_at.lock().unwrap().take() {
+                *self.paused_for.lock().unwrap() += paused_at.elapsed();
+            }
+        }
+        match next {
+            SessionState::Paused => *self.paused_at.lock().unwrap() = Some(Instant::now()),
+            SessionState::Stopping => *self.stopped_at.lock().unwrap() = Some(Local::now()),
+            _ => (),
+        }
+        *state = next;
+
+        Ok(())
+    }
+
+    // Asks the capture sources to stop, returns false if nothing was capturing.
+    pub fn request_stop(&self) -> bool {
+        let stopped = self.transition(SessionState::Stopping).is_ok();
+        if stopped {
+            self.emit(EventKind::StopRequested);
+        }
+        stopped
+    }
+
+    pub fn emit(&self, kind: EventKind) {
+        self.events.emit(Some(&self.id), kind);
+    }
+
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    // Counts a frame for the session and the recorder metrics, for backends that see frames.
+    #[cfg_attr(
+        not(any(all(windows, feature = "native"), feature = "synthetic")),
+        allow(dead_code)
+    )]
+    pub fn frame_received(&self) {
+        self.frames_received.increment();
+        self.metrics.frames_received.increment();
+    }
+
+    #[cfg_attr(
+        not(any(all(windows, feature = "native"), feature = "synthetic")),
+        allow(dead_code)
+    )]
+    pub fn frame_encoded(&self) {
+        self.frames_encoded.increment();
+        self.metrics.frames_encoded.increment();
+    }
+
+    // The frames received and encoded so far.
+    pub fn frames(&self) -> (u64, u64) {
+        (self.frames_received.get(), self.frames_encoded.get())
+    }
+
+    pub fn complete(&self, output: String) -> Result<(), anyhow::Error> {
+        self.transition(SessionState::Completed)?;
+        *self.output.lock().unwrap() = Some(output);
+        self.metrics.recordings_completed.increment();
+
+        Ok(())
+    }
+
+    pub fn fail(&self, reason: String) {
+        let mut state = self.state.lock().unwrap();
+        if state.can_transition_to(SessionState::Failed) {
+            *self.error.lock().unwrap() = Some(reason);
+            *state = SessionState::Failed;
+            self.metrics.recordings_failed.increment();
+        }
+    }
+
+    pub fn is_capturing(&self) -> bool {
+        self.state().is_capturing()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.state() == SessionState::Paused
+    }
+
+    // When the ongoing pause started, which tells it apart from the pauses before and after it.
+    pub fn paused_since(&self) -> Option<Instant> {
+        *self.paused_at.lock().unwrap()
+    }
+
+    // The total time spent paused, which the capture sources cut out of their outputs.
+    // Does not include a pause that is still ongoing.
+    pub fn paused_for(&self) -> Duration {
+        *self.paused_for.lock().unwrap()
+    }
+
+    pub fn set_video_running(&self, running: bool) {
+        *self.video_running.lock().unwrap() = running;
+    }
+
+    pub fn set_audio_running(&self, running: bool) {
+        *self.audio_running.lock().unwrap() = running;
+    }
+
+    pub fn sources_running(&self) -> bool {
+        *self.video_running.lock().unwrap() || *self.audio_running.lock().unwrap()
+    }
+
+    // Tells the capture threads to give up on a graceful stop and kill whatever they are waiting on.
+    pub fn terminate(&self) {
+        *self.terminated.lock().unwrap() = true;
+    }
+
+    pub fn is_terminated(&self) -> bool {
+        *self.terminated.lock().unwrap()
+    }
+
+    pub fn segment(&self) -> usize {
+        *self.segment.lock().unwrap()
+    }
+
+    // Moves the outputs to a new segment, returning the previous one so it can be finalized.
+    // The sources switch their outputs once they notice, see `segment_released`.
+    pub fn rotate(&self) -> Option<usize> {
+        let state = self.state.lock().unwrap();
+        if !state.is_capturing() {
+            return None;
+        }
+        let segment = {
+            let mut segment = self.segment.lock().unwrap();
+            *segment += 1;
+            *segment
+        };
+        *self.segment_started.lock().unwrap() = (Instant::now(), self.paused_for());
+        drop(state);
+        self.emit(EventKind::SegmentRotated { segment });
+
+        Some(segment - 1)
+    }
+
+    // The recorded time of the current segment, not counting pauses.
+    pub fn segment_duration(&self) -> Duration {
+        let (started, paused_before) = *self.segment_started.lock().unwrap();
+        let mut paused = self.paused_for().saturating_sub(paused_before);
+        if let Some(paused_at) = *self.paused_at.lock().unwrap() {
+            paused += paused_at.elapsed();
+        }
+        started.elapsed().saturating_sub(paused)
+    }
+
+    // The size on disk of the current segment's outputs, including the ffmpeg pause parts.
+    pub fn segment_size(&self) -> u64 {
+        let filename = self.segment_filename(self.segment());
+        let base = std::path::Path::new(&filename);
+        let (Some(folder), Some(stem)) = (base.parent(), base.file_name().and_then(|stem| stem.to_str()))
+        else {
+            return 0;
+        };
+        let prefix = format!("{stem}.");
+
+        std::fs::read_dir(folder)
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .filter(|entry| {
+                        entry
+                            .file_name()
+                            .to_str()
+                            .is_some_and(|name| name.starts_with(&prefix))
+                    })
+                    .filter_map(|entry| entry.metadata().ok())
+                    .map(|metadata| metadata.len())
+                    .sum()
+            })
+            .unwrap_or(0)
+    }
+
+    // Set by the capture backends once they write to the segment.
+    #[cfg(any(
+        all(windows, any(feature = "native", feature = "ffmpeg")),
+        feature = "x11",
+        feature = "synthetic"
+    ))]
+    pub fn set_video_segment(&self, segment: usize) {
+        *self.video_segment.lock().unwrap() = segment;
+    }
+
+    pub fn video_segment(&self) -> usize {
+        *self.video_segment.lock().unwrap()
+    }
+
+    pub fn is_video_running(&self) -> bool {
+        *self.video_running.lock().unwrap()
+    }
+
+    pub fn set_audio_segment(&self, segment: usize) {
+        *self.audio_segment.lock().unwrap() = segment;
+    }
+
+    // Whether every running source moved past the segment, so its outputs are complete.
+    pub fn segment_released(&self, segment: usize) -> bool {
+        let released = |running: &Mutex<bool>, current: &Mutex<usize>| {
+            !*running.lock().unwrap() || *current.lock().unwrap() > segment
+        };
+        released(&self.video_running, &self.video_segment)
+            && released(&self.audio_running, &self.audio_segment)
+    }
+
+    pub fn add_segment_output(&self, output: String) {
+        self.segment_outputs.lock().unwrap().push(output);
+    }
+
+    pub fn segment_outputs(&self) -> Vec<String> {
+        self.segment_outputs.lock().unwrap().clone()
+    }
+
+    // Set by the video capture once it knows which videos it writes.
+    #[cfg(any(all(windows, any(feature = "native", feature = "ffmpeg")), feature = "x11"))]
+    pub fn set_video_suffixes(&self, suffixes: Vec<String>) {
+        *self.video_suffixes.lock().unwrap() = suffixes;
+    }
+
+    pub fn video_suffixes(&self) -> Vec<String> {
+        self.video_suffixes.lock().unwrap().clone()
+    }
+
+    pub fn privacy_masks(&self) -> Vec<PrivacyMask> {
+        self.privacy_masks.lock().unwrap().clone()
+    }
+
+    // The capture hides the new masks once it notices them, see `MaskTracker`.
+    pub fn set_privacy_masks(&self, masks: Vec<PrivacyMask>) {
+        *self.privacy_masks.lock().unwrap() = masks;
+    }
+
+    // The capture blacks out the whole video once it notices, see `MaskTracker`.
+    pub fn set_video_hidden(&self, hidden: bool) {
+        *self.video_hidden.lock().unwrap() = hidden;
+    }
+
+    pub fn is_video_hidden(&self) -> bool {
+        *self.video_hidden.lock().unwrap()
+    }
+
+    pub fn add_marker(&self, marker: PauseMarker) {
+        self.markers.lock().unwrap().push(marker);
+    }
+
+    // Ends the marker that is still open, if any.
+    pub fn end_marker(&self) {
+        let mut markers = self.markers.lock().unwrap();
+        if let Some(marker) = markers.last_mut().filter(|marker| marker.ended_at.is_none()) {
+            marker.ended_at = Some(Local::now());
+        }
+    }
+
+    pub fn set_orphaned(&self, orphaned: bool) {
+        *self.orphaned.lock().unwrap() = orphaned;
+    }
+
+    pub fn is_orphaned(&self) -> bool {
+        *self.orphaned.lock().unwrap()
+    }
+}
+
+// Every session started since the recorder is up, and the one currently in progress.
+pub struct Registry {
+    sessions: Mutex<HashMap<String, Arc<Session>>>,
+    current: Mutex<Option<Arc<Session>>>,
+    events: EventBus,
+    metrics: Arc<Metrics>,
+}
+
+impl Registry {
+    pub fn new(events: EventBus, metrics: Arc<Metrics>) -> Self {
+        Registry {
+            sessions: Mutex::new(HashMap::new()),
+            current: Mutex::new(None),
+            events,
+            metrics,
+        }
+    }
+
+    // Atomically registers a new session in the `Starting` state, unless another one still holds
+    // the capture sources. A session that is only being finalized does not.
+    pub fn begin(
+        &self,
+        recordings_folder: &str,
+        settings: SessionSettings,
+    ) -> Result<Arc<Session>, anyhow::Error> {
+        let mut current = self.current.lock().unwrap();
+        if let Some(session) = current.as_ref() {
+            if session.state().is_busy() {
+                return Err(Error::msg(format!(
+                    "Session {} is still in progress",
+                    session.id
+                )));
+            }
+        }
+
+        let session = Arc::new(Session::new(
+            recordings_folder,
+            settings,
+            self.events.clone(),
+            self.metrics.clone(),
+        ));
+        session.transition(SessionState::Starting)?;
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.insert(session.id.clone(), session.clone());
+        forget_oldest(&mut sessions);
+        *current = Some(session.clone());
+
+        Ok(session)
+    }
+
+    pub fn current(&self) -> Option<Arc<Session>> {
+        self.current.lock().unwrap().clone()
+    }
+
+    pub fn get(&self, id: &str) -> Option<Arc<Session>> {
+        self.sessions.lock().unwrap().get(id).cloned()
+    }
+
+    // Every session that may still write to its outputs.
+    pub fn in_progress(&self) -> Vec<Arc<Session>> {
+        self.sessions
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|session| !session.state().is_terminal())
+            .cloned()
+            .collect()
+    }
+
+    // The state of the recorder, which is the current session's state or `Idle`.
+    pub fn state(&self) -> SessionState {
+        self.current()
+            .map(|session| session.state())
+            .unwrap_or(SessionState::Idle)
+    }
+}
+
+// Keeps the finished sessions under `KEPT_SESSIONS`, the ones in progress are always kept.
+fn forget_oldest(sessions: &mut HashMap<String, Arc<Session>>) {
+    let mut finished: Vec<(DateTime<Local>, String)> = sessions
+        .values()
+        .filter(|session| session.state().is_terminal())
+        .map(|session| (session.started_at, session.id.clone()))
+        .collect();
+    if finished.len() <= KEPT_SESSIONS {
+        return;
+    }
+    finished.sort();
+    for (_, id) in &finished[..finished.len() - KEPT_SESSIONS] {
+        sessions.remove(id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session() -> Session {
+        let settings = serde_json::from_value(serde_json::json!({
+            "capture": { "backend": "native", "bitrate": 5000000, "fps": 15 },
+            "video": true,
+            "audio": true,
+            "tag": null,
+            "metadata": {},
+            "keep_alive_timeout_in_secs": 100,
+            "limits": {}
+        }))
+        .unwrap();
+        Session::new("./recordings", settings, EventBus::new(), Arc::default())
+    }
+
+    #[test]
+    fn allows_the_recording_lifecycle() {
+        use SessionState::*;
+        for (from, to) in [
+            (Idle, Starting),
+            (Starting, Recording),
+            (Starting, Stopping),
+            (Starting, Failed),
+            (Recording, Paused),
+            (Recording, Stopping),
+            (Paused, Recording),
+            (Paused, Stopping),
+            (Stopping, Finalizing),
+            (Stopping, Failed),
+            (Finalizing, Completed),
+            (Finalizing, Failed),
+        ] {
+            assert!(from.can_transition_to(to), "{from:?} -> {to:?}");
+        }
+    }
+
+    #[test]
+    fn refuses_to_skip_or_go_back() {
+        use SessionState::*;
+        for (from, to) in [
+            (Idle, Recording),
+            (Idle, Stopping),
+            (Starting, Starting),
+            (Recording, Starting),
+            (Recording, Finalizing),
+            (Recording, Failed),
+            (Paused, Paused),
+            (Paused, Finalizing),
+            (Stopping, Recording),
+            (Stopping, Paused),
+            (Finalizing, Stopping),
+            (Completed, Starting),
+            (Failed, Starting),
+        ] {
+            assert!(!from.can_transition_to(to), "{from:?} -> {to:?}");
+        }
+    }
+
+    #[test]
+    fn keeps_the_state_after_a_refused_transition() {
+        let session = session();
+        assert!(session.transition(SessionState::Recording).is_err());
+        assert_eq!(session.state(), SessionState::Idle);
+
+        session.transition(SessionState::Starting).unwrap();
+        session.transition(SessionState::Recording).unwrap();
+        session.fail("lost the display".to_string());
+        assert_eq!(session.state(), SessionState::Recording);
+        assert_eq!(session.error(), None);
+        assert!(session.request_stop());
+        assert!(!session.request_stop());
+    }
+
+    #[test]
+    fn ends_in_a_terminal_state() {
+        let failed = session();
+        failed.transition(SessionState::Starting).unwrap();
+        failed.fail("no audio device".to_string());
+        assert_eq!(failed.state(), SessionState::Failed);
+        assert_eq!(failed.error().as_deref(), Some("no audio device"));
+        assert!(failed.state().is_terminal());
+        assert!(failed.transition(SessionState::Starting).is_err());
+
+        let completed = session();
+        completed.transition(SessionState::Starting).unwrap();
+        assert!(completed.complete("out.mp4".to_string()).is_err());
+        assert!(completed.request_stop());
+        completed.transition(SessionState::Finalizing).unwrap();
+        completed.complete("out.mp4".to_string()).unwrap();
+        assert!(completed.state().is_terminal());
+    }
+}
@@ -0,0 +1,358 @@
+use anyhow::Error;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{
+    capture::{displays::MAX_SIZE, window::WindowMatcher},
+    config::{
+        CaptureConfig, CaptureRegion, ClickHighlight, Config, DirtyRegionMode, DisplayLayout,
+        DisplaySelection, FrameFormat, MaskArea, OutputSize, PrivacyMask, ScaleMode, SessionLimits,
+        Watermark, WindowTarget,
+    },
+};
+
+// The optional body of POST /start, every field falls back to config.json.
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct StartOptions {
+    pub fps: Option<u32>,
+    pub bitrate: Option<u32>,
+    // The name of one of the configured backends.
+    pub backend: Option<String>,
+    // A display index or name, or "all", see GET /displays.
+    pub display: Option<DisplaySelection>,
+    pub layout: Option<DisplayLayout>,
+    pub region: Option<CaptureRegion>,
+    pub output: Option<OutputSize>,
+    pub scale: Option<ScaleMode>,
+    // Captures a window instead of the displays.
+    pub window: Option<WindowTarget>,
+    pub cursor: Option<bool>,
+    pub border: Option<bool>,
+    pub min_update_interval_in_ms: Option<u64>,
+    pub dirty_region: Option<DirtyRegionMode>,
+    pub color_format: Option<FrameFormat>,
+    pub click_highlight: Option<ClickHighlight>,
+    pub watermark: Option<Watermark>,
+    // Replaces the configured masks.
+    pub privacy_masks: Option<Vec<PrivacyMask>>,
+    pub audio: Option<bool>,
+    pub video: Option<bool>,
+    pub tag: Option<String>,
+    pub metadata: Option<Map<String, Value>>,
+    pub keep_alive_timeout_in_secs: Option<u64>,
+    pub limits: Option<SessionLimits>,
+}
+
+// The effective settings of a session, after applying the start options over the config.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct SessionSettings {
+    pub capture: CaptureConfig,
+    pub video: bool,
+    pub audio: bool,
+    pub tag: Option<String>,
+    pub metadata: Map<String, Value>,
+    // The TTL of the session's keep-alive leases.
+    pub keep_alive_timeout_in_secs: u64,
+    pub limits: SessionLimits,
+}
+
+const MAX_TAG_LENGTH: usize = 64;
+const MAX_WATERMARK_LABEL_LENGTH: usize = 100;
+const MAX_WATERMARK_SCALE: u32 = 8;
+// Every mask is a filter of the ffmpeg backends' filtergraph.
+const MAX_PRIVACY_MASKS: usize = 32;
+
+impl StartOptions {
+    pub fn resolve(self, config: &Config) -> Result<SessionSettings, anyhow::Error> {
+        let limits = config.capture_limits;
+        let mut capture = config.capture.clone();
+
+        if let Some(fps) = self.fps {
+            if fps == 0 || fps > limits.max_fps {
+                return Err(Error::msg(format!(
+                    "fps must be between 1 and {}",
+                    limits.max_fps
+                )));
+            }
+            capture.fps = fps;
+        }
+        if let Some(bitrate) = self.bitrate {
+            if bitrate == 0 || bitrate > limits.max_bitrate {
+                return Err(Error::msg(format!(
+                    "bitrate must be between 1 and {}",
+                    limits.max_bitrate
+                )));
+            }
+            capture.bitrate = bitrate;
+        }
+        if let Some(backend) = self.backend {
+            capture.backend = backend;
+        }
+        if let Some(display) = self.display {
+            capture.display = Some(display);
+        }
+        if let Some(layout) = self.layout {
+            capture.layout = layout;
+        }
+        if let Some(region) = self.region {
+            capture.region = Some(region);
+        }
+        if let Some(output) = self.output {
+            capture.output = Some(output);
+        }
+        if let Some(scale) = self.scale {
+            capture.scale = scale;
+        }
+        if let Some(window) = self.window {
+            capture.window = Some(window);
+        }
+        if let Some(cursor) = self.cursor {
+            capture.cursor = cursor;
+        }
+        if let Some(border) = self.border {
+            capture.border = border;
+        }
+        if let Some(interval) = self.min_update_interval_in_ms {
+            capture.min_update_interval_in_ms = Some(interval);
+        }
+        if let Some(dirty_region) = self.dirty_region {
+            capture.dirty_region = dirty_region;
+        }
+        if let Some(color_format) = self.color_format {
+            capture.color_format = color_format;
+        }
+        if let Some(click_highlight) = self.click_highlight {
+            capture.click_highlight = Some(click_highlight);
+        }
+        if let Some(watermark) = self.watermark {
+            capture.watermark = Some(watermark);
+        }
+        if let Some(privacy_masks) = self.privacy_masks {
+            capture.privacy_masks = privacy_masks;
+        }
+        // Whether the region fits is only known once the displays are, when the capture starts.
+        let within = |offset: u32, size: u32| {
+            size >= 2 && offset.checked_add(size).is_some_and(|end| end <= MAX_SIZE)
+        };
+        if capture.region.is_some_and(|region| {
+            !within(region.x, region.width) || !within(region.y, region.height)
+        }) {
+            return Err(Error::msg(format!(
+                "region must be at least 2x2 and end within {MAX_SIZE}x{MAX_SIZE}"
+            )));
+        }
+        if capture
+            .output
+            .is_some_and(|output| !within(0, output.width) || !within(0, output.height))
+        {
+            return Err(Error::msg(format!(
+                "output must be between 2x2 and {MAX_SIZE}x{MAX_SIZE}"
+            )));
+        }
+        if let Some(window) = &capture.window {
+            WindowMatcher::new(window.title.as_deref(), window.process.as_deref())?;
+        }
+        if capture
+            .click_highlight
+            .is_some_and(|highlight| highlight.radius == 0 || highlight.duration_in_ms == 0)
+        {
+            return Err(Error::msg(
+                "click_highlight radius and duration_in_ms must be positive",
+            ));
+        }
+        if let Some(watermark) = &capture.watermark {
+            validate_watermark(watermark)?;
+        }
+        validate_privacy_masks(&capture.privacy_masks)?;
+        if !config.backends.contains_key(&capture.backend) {
+            return Err(Error::msg(format!(
+                "backend {} is not configured",
+                capture.backend
+            )));
+        }
+
+        let video = self.video.unwrap_or(true);
+        let audio = self.audio.unwrap_or(true);
+        if !video && !audio {
+            return Err(Error::msg("At least one of video or audio must be recorded"));
+        }
+
+        if let Some(tag) = &self.tag {
+            if tag.is_empty()
+                || tag.len() > MAX_TAG_LENGTH
+                || !tag
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            {
+                return Err(Error::msg(format!(
+                    "tag must be 1 to {MAX_TAG_LENGTH} letters, digits, '-' or '_'"
+                )));
+            }
+        }
+
+        let keep_alive_timeout_in_secs = self
+            .keep_alive_timeout_in_secs
+            .unwrap_or(config.keep_alive_timeout_in_secs);
+        if keep_alive_timeout_in_secs == 0
+            || keep_alive_timeout_in_secs > limits.max_keep_alive_timeout_in_secs
+        {
+            return Err(Error::msg(format!(
+                "keep_alive_timeout_in_secs must be between 1 and {}",
+                limits.max_keep_alive_timeout_in_secs
+            )));
+        }
+
+        let session_limits = self.limits.unwrap_or(config.session_limits);
+        if session_limits.max_duration_in_secs == Some(0)
+            || session_limits.max_size_in_bytes == Some(0)
+        {
+            return Err(Error::msg("Session limits must be positive"));
+        }
+
+        Ok(SessionSettings {
+            capture,
+            video,
+            audio,
+            tag: self.tag,
+            metadata: self.metadata.unwrap_or_default(),
+            keep_alive_timeout_in_secs,
+            limits: session_limits,
+        })
+    }
+}
+
+fn validate_watermark(watermark: &Watermark) -> Result<(), anyhow::Error> {
+    if watermark.scale == 0 || watermark.scale > MAX_WATERMARK_SCALE {
+        return Err(Error::msg(format!(
+            "watermark scale must be between 1 and {MAX_WATERMARK_SCALE}"
+        )));
+    }
+    let labels = [&watermark.station_id, &watermark.text, &watermark.font_file];
+    for label in labels.into_iter().flatten() {
+        if label.chars().count() > MAX_WATERMARK_LABEL_LENGTH
+            || label.chars().any(|c| c.is_control())
+        {
+            return Err(Error::msg(format!(
+                "watermark station_id, text and font_file must be at most {MAX_WATERMARK_LABEL_LENGTH} characters, without control characters"
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+// Also checks the masks a running session gets.
+pub fn validate_privacy_masks(masks: &[PrivacyMask]) -> Result<(), anyhow::Error> {
+    if masks.len() > MAX_PRIVACY_MASKS {
+        return Err(Error::msg(format!(
+            "privacy_masks may hold at most {MAX_PRIVACY_MASKS} masks"
+        )));
+    }
+    for (index, mask) in masks.iter().enumerate() {
+        match &mask.area {
+            MaskArea::Rect { width, height, .. } if *width == 0 || *height == 0 => {
+                return Err(Error::msg(format!(
+                    "privacy mask {index} must have a positive width and height"
+                )));
+            }
+            MaskArea::Rect { .. } => (),
+            MaskArea::Window { title, process } => {
+                WindowMatcher::new(title.as_deref(), process.as_deref())
+                    .map_err(|err| Error::msg(format!("privacy mask {index}, {err}")))?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn config() -> Config {
+        serde_json::from_value(json!({
+            "recordings_folder": "./recordings",
+            "keep_alive_timeout_in_secs": 100,
+            "capture": { "backend": "native", "bitrate": 5000000, "fps": 15 },
+            "backends": { "native": { "kind": "native" }, "ffmpeg": { "kind": "ffmpeg" } },
+            "capture_limits": {
+                "max_fps": 60,
+                "max_bitrate": 50000000,
+                "max_keep_alive_timeout_in_secs": 86400
+            }
+        }))
+        .unwrap()
+    }
+
+    fn resolve(options: Value) -> Result<SessionSettings, anyhow::Error> {
+        serde_json::from_value::<StartOptions>(options)
+            .unwrap()
+            .resolve(&config())
+    }
+
+    #[test]
+    fn falls_back_to_the_config() {
+        let settings = resolve(json!({})).unwrap();
+        assert_eq!(settings.capture.fps, 15);
+        assert_eq!(settings.capture.backend, "native");
+        assert!(settings.video && settings.audio);
+        assert_eq!(settings.keep_alive_timeout_in_secs, 100);
+    }
+
+    #[test]
+    fn overrides_the_config() {
+        let settings = resolve(json!({
+            "fps": 30,
+            "backend": "ffmpeg",
+            "audio": false,
+            "tag": "demo-1"
+        }))
+        .unwrap();
+        assert_eq!(settings.capture.fps, 30);
+        assert_eq!(settings.capture.backend, "ffmpeg");
+        assert!(settings.video && !settings.audio);
+        assert_eq!(settings.tag.as_deref(), Some("demo-1"));
+    }
+
+    #[test]
+    fn takes_only_the_8_bit_color_formats() {
+        let settings = resolve(json!({ "color_format": "rgba8" })).unwrap();
+        assert_eq!(settings.capture.color_format, FrameFormat::Rgba8);
+        let options = json!({ "color_format": "rgba16f" });
+        assert!(serde_json::from_value::<StartOptions>(options).is_err());
+    }
+
+    #[test]
+    fn rejects_values_out_of_the_limits() {
+        assert!(resolve(json!({ "fps": 0 })).is_err());
+        assert!(resolve(json!({ "fps": 61 })).is_err());
+        assert!(resolve(json!({ "bitrate": 0 })).is_err());
+        assert!(resolve(json!({ "bitrate": 50000001 })).is_err());
+        assert!(resolve(json!({ "keep_alive_timeout_in_secs": 0 })).is_err());
+        assert!(resolve(json!({ "keep_alive_timeout_in_secs": 86401 })).is_err());
+    }
+
+    #[test]
+    fn rejects_what_can_not_be_recorded() {
+        assert!(resolve(json!({ "video": false, "audio": false })).is_err());
+        assert!(resolve(json!({ "backend": "missing" })).is_err());
+        assert!(resolve(json!({ "tag": "no spaces" })).is_err());
+        assert!(resolve(json!({ "tag": "" })).is_err());
+    }
+
+    #[test]
+    fn rejects_regions_and_outputs_beyond_the_largest_video() {
+        let region = |x: u32, width: u32| json!({ "x": x, "y": 0, "width": width, "height": 100 });
+        assert!(resolve(json!({ "region": region(0, 1920) })).is_ok());
+        assert!(resolve(json!({ "region": region(0, 1) })).is_err());
+        assert!(resolve(json!({ "region": region(u32::MAX, 2) })).is_err());
+        assert!(resolve(json!({ "region": region(MAX_SIZE - 1, 2) })).is_err());
+        assert!(resolve(json!({ "output": { "width": 1280, "height": 720 } })).is_ok());
+        assert!(resolve(json!({ "output": { "width": 1, "height": 720 } })).is_err());
+        assert!(resolve(json!({ "output": { "width": MAX_SIZE + 1, "height": 720 } })).is_err());
+    }
+}
@@ -0,0 +1,374 @@
+use anyhow::Error;
+use log::{error, info, warn};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::{
+    io::Write,
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    capture::{
+        clicks::ClickOverlay,
+        displays::{self, DisplayInfo},
+        masks::{self, MaskTracker},
+        mouse::MouseTracker,
+        watermark::WatermarkOverlay,
+        Capabilities, CaptureBackend,
+    },
+    config::{CaptureConfig, DisplayLayout},
+    events::EventKind,
+    session::Session,
+};
+
+// The largest pattern generated, 8K. A frame is allocated whole, at four bytes per pixel.
+const MAX_SIZE: (u32, u32) = (7680, 4320);
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+struct SyntheticSettings {
+    // Encodes the generated frames, so it is the only thing the backend needs.
+    executable: String,
+    width: u32,
+    height: u32,
+}
+
+impl SyntheticSettings {
+    // The bytes of a BGRA frame, None when it does not fit in memory at all.
+    fn frame_size(&self) -> Option<usize> {
+        (self.width as usize)
+            .checked_mul(self.height as usize)?
+            .checked_mul(4)
+    }
+}
+
+impl Default for SyntheticSettings {
+    fn default() -> Self {
+        SyntheticSettings {
+            executable: "ffmpeg".to_string(),
+            width: 1280,
+            height: 720,
+        }
+    }
+}
+
+// Generates a test pattern instead of capturing a display, for headless machines and CI.
+struct SyntheticBackend {
+    settings: SyntheticSettings,
+}
+
+pub fn backend(settings: Map<String, Value>) -> Result<Arc<dyn CaptureBackend>, anyhow::Error> {
+    let settings: SyntheticSettings = serde_json::from_value(Value::Object(settings))?;
+    if settings.width == 0
+        || settings.height == 0
+        || settings.width % 2 == 1
+        || settings.height % 2 == 1
+        || settings.width > MAX_SIZE.0
+        || settings.height > MAX_SIZE.1
+        || settings.frame_size().is_none()
+    {
+        return Err(Error::msg(format!(
+            "width and height must be positive, even and at most {}x{}",
+            MAX_SIZE.0, MAX_SIZE.1
+        )));
+    }
+
+    Ok(Arc::new(SyntheticBackend { settings }))
+}
+
+impl CaptureBackend for SyntheticBackend {
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            pause: true,
+            rotation: true,
+            frame_stats: true,
+            window: false,
+            click_highlight: true,
+            window_masks: false,
+        }
+    }
+
+    fn start(
+        &self,
+        session: Arc<Session>,
+        filename: String,
+        capture_config: CaptureConfig,
+    ) -> Result<(), anyhow::Error> {
+        // There is a single display, but a selection that does not match it is still an error.
+        let selected = displays::select(self.displays()?, capture_config.display.as_ref())?;
+        let region = displays::regions(&selected, DisplayLayout::Composite)[0]
+            .crop(capture_config.region.as_ref())?;
+        let (width, height) = displays::scaled_size(
+            region.width,
+            region.height,
+            capture_config.output,
+            capture_config.scale,
+        );
+        // The pattern is generated whole, ffmpeg crops and scales it.
+        let filter = format!(
+            "crop={}:{}:{}:{},scale={width}:{height}",
+            region.width, region.height, region.x, region.y
+        );
+        // Drawn into the recorded part of the pattern, so it is scaled along with it.
+        let watermark = capture_config
+            .watermark
+            .as_ref()
+            .map(|watermark| WatermarkOverlay::new(watermark, &session.id));
+        let area = (
+            region.x as usize,
+            region.y as usize,
+            region.width as usize,
+            region.height as usize,
+        );
+        // The pattern is the whole virtual screen, its masks are hidden before ffmpeg scales it.
+        let masks = MaskTracker::start(session.clone());
+        let settings = self.settings.clone();
+        // A made up mouse clicks on the pattern, so the rings can be seen without a display.
+        let clicks = capture_config.click_highlight.map(|highlight| {
+            let mouse = MouseTracker::simulated(session.clone(), (settings.width, settings.height));
+            ClickOverlay::new(highlight, mouse)
+        });
+        let mut encoder = Encoder::start(&settings, &capture_config, &filter, &filename)?;
+        session.set_video_running(true);
+        info!(
+            "Starting a synthetic {}x{} capture at {} fps",
+            settings.width, settings.height, capture_config.fps
+        );
+
+        thread::spawn(move || {
+            let frame_interval = Duration::from_secs_f64(1.0 / capture_config.fps as f64);
+            let mut frame = vec![0u8; settings.frame_size().unwrap_or_default()];
+            let mut segment = session.segment();
+            let mut index: u64 = 0;
+            let mut next_frame = Instant::now();
+
+            while session.is_capturing() {
+                let current = session.segment();
+                if current != segment {
+                    let filename = format!("{}.mp4", session.segment_filename(current));
+                    match Encoder::start(&settings, &capture_config, &filter, &filename) {
+                        Ok(next) => {
+                            std::mem::replace(&mut encoder, next).finish(&session);
+                            segment = current;
+                            session.set_video_segment(segment);
+                            info!("Capture moved to {filename}");
+                        }
+                        Err(err) => {
+                            error!("Could not start the next segment, {err}");
+                            session.request_stop();
+                            break;
+                        }
+                    }
+                }
+
+                next_frame += frame_interval;
+                match next_frame.checked_duration_since(Instant::now()) {
+                    Some(wait) => thread::sleep(wait),
+                    // Fell behind, skip the missed frames rather than bursting them.
+                    None => next_frame = Instant::now(),
+                }
+
+                session.frame_received();
+                if session.is_paused() {
+                    continue;
+                }
+                draw_frame(&mut frame, &settings, index, capture_config.fps);
+                for mask in masks.current() {
+                    let hidden = mask.within((0, 0));
+                    masks::hide(&mut frame, settings.width as usize, hidden, mask.style, false);
+                }
+                if let Some(clicks) = &clicks {
+                    let rings = clicks.rings((0, 0), |x, y| (x, y, 1.0));
+                    let size = (settings.width as usize, settings.height as usize);
+                    clicks.draw(&mut frame, size, rings, false);
+                }
+                if let Some(watermark) = &watermark {
+                    watermark.draw(&mut frame, settings.width as usize, area, false);
+                }
+                if let Err(err) = encoder.stdin.write_all(&frame) {
+                    error!("Could not send a frame to ffmpeg, {err}");
+                    session.request_stop();
+                    break;
+                }
+                if index == 0 {
+                    session.emit(EventKind::FirstFrameReceived);
+                }
+                index += 1;
+                session.frame_encoded();
+            }
+
+            encoder.finish(&session);
+            session.request_stop();
+            session.set_video_running(false);
+            info!("Synthetic capture is done, generated {index} frames");
+        });
+
+        Ok(())
+    }
+
+    fn displays(&self) -> Result<Vec<DisplayInfo>, anyhow::Error> {
+        Ok(vec![DisplayInfo {
+            index: 0,
+            name: "synthetic".to_string(),
+            description: Some("Test pattern".to_string()),
+            x: 0,
+            y: 0,
+            width: self.settings.width,
+            height: self.settings.height,
+            primary: true,
+        }])
+    }
+}
+
+// An ffmpeg process encoding the raw BGRA frames written to its stdin.
+struct Encoder {
+    child: Child,
+    stdin: ChildStdin,
+}
+
+impl Encoder {
+    fn start(
+        settings: &SyntheticSettings,
+        capture_config: &CaptureConfig,
+        filter: &str,
+        filename: &str,
+    ) -> Result<Self, anyhow::Error> {
+        let mut child = Command::new(&settings.executable)
+            .args(["-loglevel", "warning", "-f", "rawvideo", "-pix_fmt", "bgra"])
+            .args(["-s", &format!("{}x{}", settings.width, settings.height)])
+            .args([
+                "-r",
+                &capture_config.fps.to_string(),
+                "-i",
+                "-",
+                "-vf",
+                filter,
+            ])
+            .args(["-b:v", &capture_config.bitrate.to_string()])
+            .args(["-pix_fmt", "yuv420p", "-y", filename])
+            .stdin(Stdio::piped())
+            .spawn()
+            .or(Err(Error::msg(
+                "Could not start ffmpeg for the synthetic capture",
+            )))?;
+        let stdin = child
+            .stdin
+            .take()
+            .ok_or(Error::msg("ffmpeg has no stdin"))?;
+
+        Ok(Encoder { child, stdin })
+    }
+
+    // Closing stdin ends the input, ffmpeg then finalizes the output and exits.
+    fn finish(self, session: &Session) {
+        let Encoder { mut child, stdin } = self;
+        drop(stdin);
+
+        while let Ok(None) = child.try_wait() {
+            if session.is_terminated() {
+                warn!("Killing ffmpeg");
+                let _ = child.kill();
+                let _ = child.wait();
+                break;
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+    }
+}
+
+// BGRA, from white to black like the usual color bars.
+const BARS: [[u8; 4]; 8] = [
+    [255, 255, 255, 255],
+    [0, 255, 255, 255],
+    [255, 255, 0, 255],
+    [0, 255, 0, 255],
+    [255, 0, 255, 255],
+    [0, 0, 255, 255],
+    [255, 0, 0, 255],
+    [0, 0, 0, 255],
+];
+
+// 3x5 glyphs of the digits, a row per byte.
+const DIGITS: [[u8; 5]; 10] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b001, 0b001, 0b001],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+];
+
+// Color bars, a line sweeping across them once a second and the frame number, so dropped or
+// repeated frames show in the output.
+fn draw_frame(frame: &mut [u8], settings: &SyntheticSettings, index: u64, fps: u32) {
+    let (width, height) = (settings.width as usize, settings.height as usize);
+    let sweep = (index % fps as u64) as usize * width / fps as usize;
+
+    for (y, row) in frame.chunks_exact_mut(width * 4).enumerate() {
+        for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
+            let color = if x.abs_diff(sweep) < 2 {
+                [255, 255, 255, 255]
+            } else if y > height * 3 / 4 {
+                // A gray ramp under the bars.
+                let level = (x * 255 / width) as u8;
+                [level, level, level, 255]
+            } else {
+                BARS[x * BARS.len() / width]
+            };
+            pixel.copy_from_slice(&color);
+        }
+    }
+
+    let scale = (height / 40).max(1);
+    let digits = index.to_string();
+    let (left, top) = (scale * 2, scale * 2);
+    fill(
+        frame,
+        width,
+        (left - scale, top - scale),
+        (digits.len() * 4 * scale + scale, 7 * scale),
+        [0, 0, 0, 255],
+    );
+    for (position, digit) in digits.bytes().enumerate() {
+        let glyph = DIGITS[(digit - b'0') as usize];
+        for (glyph_y, bits) in glyph.iter().enumerate() {
+            for glyph_x in 0..3 {
+                if bits & (0b100 >> glyph_x) != 0 {
+                    fill(
+                        frame,
+                        width,
+                        (
+                            left + (position * 4 + glyph_x) * scale,
+                            top + glyph_y * scale,
+                        ),
+                        (scale, scale),
+                        [255, 255, 255, 255],
+                    );
+                }
+            }
+        }
+    }
+}
+
+fn fill(
+    frame: &mut [u8],
+    width: usize,
+    (left, top): (usize, usize),
+    (fill_width, fill_height): (usize, usize),
+    color: [u8; 4],
+) {
+    let height = frame.len() / (width * 4);
+    for y in top..(top + fill_height).min(height) {
+        for x in left..(left + fill_width).min(width) {
+            let offset = (y * width + x) * 4;
+            frame[offset..offset + 4].copy_from_slice(&color);
+        }
+    }
+}
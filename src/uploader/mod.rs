@@ -1,55 +1,91 @@
-// For future use, when we have the vod-hub planned to happen.
+use std::{path::Path, sync::Arc, time::Duration};
 
-use std::time::Duration;
-
-use reqwest::{header::{HeaderMap, HeaderValue, CONTENT_TYPE}, StatusCode};
-use reqwest::{multipart, Client, Body};
+use log::{error, info};
+use reqwest::{multipart, Body, Client};
 use tokio::fs::File;
 use tokio_util::codec::{BytesCodec, FramedRead};
 
-pub fn uploader_task(recordings_folder: &String) {
-    tokio::spawn(async {
+use crate::{
+    api::AppState,
+    config::UploadConfig,
+    recordings::{self, UploadState},
+};
+
+// Uploads the finalized recordings every `interval_in_secs`. An uploaded recording gets a marker
+// next to it, which is how it is skipped afterwards and how `UploadFinished` gets emitted.
+pub fn uploader_task(state: Arc<AppState>) {
+    let Some(config) = state.config.upload.clone() else {
+        return;
+    };
+
+    tokio::spawn(async move {
+        let client = Client::new();
+        let mut interval = tokio::time::interval(Duration::from_secs(config.interval_in_secs));
         loop {
-            tokio::time::sleep(Duration::from_secs(1 * 60)).await;
-            upload_local_files().await
+            interval.tick().await;
+            upload_local_files(&state, &client, &config).await;
         }
     });
 }
 
-async fn upload_local_files() {
-
-}
+async fn upload_local_files(state: &AppState, client: &Client, config: &UploadConfig) {
+    let folder = &state.config.recordings_folder;
+    let recordings = match recordings::list(folder) {
+        Ok(recordings) => recordings,
+        Err(err) => {
+            error!("Could not list the recordings to upload, {err}");
+            return;
+        }
+    };
 
-async fn remove_file() {
+    for recording in recordings {
+        // Parts, and outputs that are still being written or combined, are never finalized.
+        if !recording.finalized || recording.upload != UploadState::Pending {
+            continue;
+        }
+        let Ok(path) = recordings::resolve(folder, &recording.name) else {
+            continue;
+        };
 
+        match upload_file(client, config, &path).await {
+            Ok(()) => {
+                if let Err(err) = std::fs::write(recordings::upload_marker(&path), "") {
+                    error!("Could not mark {} as uploaded, {err}", recording.name);
+                }
+                info!("Uploaded {}", recording.name);
+            }
+            Err(err) => error!("Could not upload {}, {err}", recording.name),
+        }
+    }
 }
 
-async fn upload_file(file_name: String) -> Result<bool, Box<dyn std::error::Error>> {
-    let client = Client::new();
-    let file = File::open(file_name.clone()).await?;
+async fn upload_file(
+    client: &Client,
+    config: &UploadConfig,
+    path: &Path,
+) -> Result<(), anyhow::Error> {
+    let file = File::open(path).await?;
     let stream = FramedRead::new(file, BytesCodec::new());
-    let file_body = Body::wrap_stream(stream);
+    let file_name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default()
+        .to_string();
 
-    let file_part = multipart::Part::stream(file_body)
+    let file_part = multipart::Part::stream(Body::wrap_stream(stream))
         .file_name(file_name)
-        .mime_str("video/mp4")?;
-
-        let form = reqwest::multipart::Form::new()
-        .text("stationId", "123")
+        .mime_str(recordings::content_type(path))?;
+    let form = multipart::Form::new()
+        .text("stationId", config.station_id.clone())
         .part("file", file_part);
 
-    let mut headers = HeaderMap::new();
-    headers.insert(
-        CONTENT_TYPE,
-        HeaderValue::from_static("multipart/form-data"),
-    );
-
-    let res = client
-        .post("http://localhost:3000/files")
+    // The multipart body sets its own content type, boundary included.
+    client
+        .post(&config.url)
         .multipart(form)
         .send()
-        .await?;
+        .await?
+        .error_for_status()?;
 
-    info!("{}", res.status());
-    Ok(res.status() == StatusCode::OK)
-}
\ No newline at end of file
+    Ok(())
+}
@@ -0,0 +1,311 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use chrono::{DateTime, Local, TimeDelta};
+use hmac::{Hmac, Mac};
+use log::{error, info, warn};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use tokio::sync::broadcast::error::RecvError;
+use uuid::Uuid;
+
+use crate::{
+    api::auth::{SIGNATURE_HEADER, TIMESTAMP_HEADER},
+    config::{WebhookEndpoint, WebhooksConfig},
+    events::{Event, EventBus},
+};
+
+pub const EVENT_HEADER: &str = "x-recorder-event";
+pub const DELIVERY_HEADER: &str = "x-recorder-delivery";
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const FIRST_RETRY_DELAY_IN_SECS: i64 = 5;
+const MAX_RETRY_DELAY_IN_SECS: i64 = 10 * 60;
+
+// An event waiting to be delivered to an endpoint.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+struct Delivery {
+    id: String,
+    // The endpoint's url, its secret is looked up in the config when sending.
+    url: String,
+    event_type: String,
+    body: String,
+    attempts: u32,
+    next_attempt_at: DateTime<Local>,
+}
+
+// Delivers the recorder events to the configured endpoints. Failed deliveries are retried with an
+// exponential backoff, from a queue that is persisted to `queue_path`.
+pub fn start(config: WebhooksConfig, events: EventBus) {
+    if config.endpoints.is_empty() {
+        return;
+    }
+    let mut receiver = events.subscribe();
+    let config = Arc::new(config);
+    let queue = Arc::new(Mutex::new(load_queue(&config.queue_path)));
+    let undelivered = queue.lock().unwrap().len();
+    if undelivered > 0 {
+        info!("Resuming {undelivered} undelivered webhooks");
+    }
+
+    // The events are queued on a task of their own, a slow endpoint would make the receiver lag.
+    let (queued_config, queued) = (config.clone(), queue.clone());
+    tokio::spawn(async move {
+        loop {
+            match receiver.recv().await {
+                Ok(event) => {
+                    let mut queue = queued.lock().unwrap();
+                    queue.extend(deliveries(&queued_config.endpoints, &event));
+                    save_queue(&queued_config.queue_path, &queue);
+                }
+                Err(RecvError::Lagged(missed)) => {
+                    warn!("Webhooks fell behind, {missed} events were not delivered");
+                }
+                Err(RecvError::Closed) => break,
+            }
+        }
+    });
+
+    tokio::spawn(async move {
+        let client = Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .expect("The webhooks client should build");
+        let mut interval = tokio::time::interval(Duration::from_secs(1));
+        loop {
+            interval.tick().await;
+            deliver_due(&client, &config, &queue).await;
+        }
+    });
+}
+
+fn deliveries(endpoints: &[WebhookEndpoint], event: &Event) -> Vec<Delivery> {
+    let Ok(body) = serde_json::to_value(event) else {
+        return vec![];
+    };
+    let event_type = body["type"].as_str().unwrap_or_default().to_string();
+
+    endpoints
+        .iter()
+        .filter(|endpoint| endpoint.events.is_empty() || endpoint.events.contains(&event_type))
+        .map(|endpoint| Delivery {
+            id: Uuid::new_v4().to_string(),
+            url: endpoint.url.clone(),
+            event_type: event_type.clone(),
+            body: body.to_string(),
+            attempts: 0,
+            next_attempt_at: Local::now(),
+        })
+        .collect()
+}
+
+// Sends every delivery that is due. They stay queued while they are sent, so they are persisted
+// along with the events that arrive meanwhile.
+async fn deliver_due(client: &Client, config: &WebhooksConfig, queue: &Mutex<Vec<Delivery>>) {
+    let now = Local::now();
+    let due: Vec<Delivery> = queue
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|delivery| delivery.next_attempt_at <= now)
+        .cloned()
+        .collect();
+    if due.is_empty() {
+        return;
+    }
+
+    // The deliveries to retry by id, None for the ones that are done with.
+    let mut sent: HashMap<String, Option<Delivery>> = HashMap::new();
+    for mut delivery in due {
+        let Some(endpoint) = config
+            .endpoints
+            .iter()
+            .find(|endpoint| endpoint.url == delivery.url)
+        else {
+            warn!(
+                "Dropping webhook {}, {} is no longer configured",
+                delivery.id, delivery.url
+            );
+            sent.insert(delivery.id, None);
+            continue;
+        };
+
+        match send(client, endpoint, &delivery).await {
+            Ok(()) => {
+                sent.insert(delivery.id, None);
+            }
+            Err(err) => {
+                delivery.attempts += 1;
+                if delivery.attempts >= config.max_attempts {
+                    error!(
+                        "Dropping webhook {} to {} after {} attempts, {err}",
+                        delivery.id, delivery.url, delivery.attempts
+                    );
+                    sent.insert(delivery.id, None);
+                    continue;
+                }
+                let delay = (FIRST_RETRY_DELAY_IN_SECS << (delivery.attempts - 1).min(16))
+                    .min(MAX_RETRY_DELAY_IN_SECS);
+                warn!(
+                    "Webhook {} to {} failed, retrying in {delay} seconds, {err}",
+                    delivery.id, delivery.url
+                );
+                delivery.next_attempt_at = Local::now() + TimeDelta::seconds(delay);
+                sent.insert(delivery.id.clone(), Some(delivery));
+            }
+        }
+    }
+
+    let mut queue = queue.lock().unwrap();
+    queue.retain_mut(|queued| match sent.remove(&queued.id) {
+        Some(Some(retry)) => {
+            *queued = retry;
+            true
+        }
+        Some(None) => false,
+        None => true,
+    });
+    save_queue(&config.queue_path, &queue);
+}
+
+// The signature is the hex HMAC-SHA256 of "{timestamp}\n{body}", keyed with the endpoint's
+// secret. The timestamp is in unix seconds.
+async fn send(
+    client: &Client,
+    endpoint: &WebhookEndpoint,
+    delivery: &Delivery,
+) -> Result<(), anyhow::Error> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+        .to_string();
+    let mut request = client
+        .post(&endpoint.url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(EVENT_HEADER, &delivery.event_type)
+        .header(DELIVERY_HEADER, &delivery.id)
+        .header(TIMESTAMP_HEADER, &timestamp);
+    if let Some(secret) = &endpoint.secret {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())?;
+        mac.update(format!("{timestamp}\n").as_bytes());
+        mac.update(delivery.body.as_bytes());
+        request = request.header(SIGNATURE_HEADER, hex::encode(mac.finalize().into_bytes()));
+    }
+
+    request
+        .body(delivery.body.clone())
+        .send()
+        .await?
+        .error_for_status()?;
+
+    Ok(())
+}
+
+fn load_queue(path: &str) -> Vec<Delivery> {
+    match std::fs::read_to_string(path) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|err| {
+            error!("Could not read the webhooks queue {path}, {err}");
+            vec![]
+        }),
+        Err(_) => vec![],
+    }
+}
+
+// Writes the queue next to its path first, so a crash never leaves half of it behind.
+fn save_queue(path: &str, queue: &[Delivery]) {
+    let temporary = format!("{path}.tmp");
+    let result = serde_json::to_string(queue)
+        .map_err(anyhow::Error::from)
+        .and_then(|content| Ok(std::fs::write(&temporary, content)?))
+        .and_then(|_| Ok(std::fs::rename(&temporary, path)?));
+    if let Err(err) = result {
+        error!("Could not persist the webhooks queue to {path}, {err}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Nothing listens on the discard port, so every send fails right away.
+    const UNREACHABLE: &str = "http://127.0.0.1:9/hooks";
+
+    // Every call gets a queue file of its own, which `deliver` removes.
+    fn config(max_attempts: u32) -> WebhooksConfig {
+        let queue_path = std::env::temp_dir().join(format!("{}.json", Uuid::new_v4()));
+        WebhooksConfig {
+            endpoints: vec![WebhookEndpoint {
+                url: UNREACHABLE.to_string(),
+                secret: Some("secret".to_string()),
+                events: vec![],
+            }],
+            queue_path: queue_path.to_string_lossy().into_owned(),
+            max_attempts,
+        }
+    }
+
+    async fn deliver(config: WebhooksConfig, queue: Vec<Delivery>) -> Vec<Delivery> {
+        let queue = Mutex::new(queue);
+        deliver_due(&Client::new(), &config, &queue).await;
+        let _ = std::fs::remove_file(&config.queue_path);
+        queue.into_inner().unwrap()
+    }
+
+    fn delivery(url: &str, attempts: u32) -> Delivery {
+        Delivery {
+            id: Uuid::new_v4().to_string(),
+            url: url.to_string(),
+            event_type: "session_started".to_string(),
+            body: "{}".to_string(),
+            attempts,
+            next_attempt_at: Local::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn backs_off_exponentially_up_to_the_max_delay() {
+        let queue = vec![
+            delivery(UNREACHABLE, 0),
+            delivery(UNREACHABLE, 3),
+            delivery(UNREACHABLE, 12),
+        ];
+        let before = Local::now();
+        let queue = deliver(config(20), queue).await;
+
+        let delays: Vec<(u32, i64)> = queue
+            .iter()
+            .map(|delivery| {
+                let delay = (delivery.next_attempt_at - before).num_seconds();
+                (delivery.attempts, delay)
+            })
+            .collect();
+        assert_eq!(delays, [(1, 5), (4, 40), (13, MAX_RETRY_DELAY_IN_SECS)]);
+    }
+
+    #[tokio::test]
+    async fn waits_for_the_next_attempt() {
+        let mut later = delivery(UNREACHABLE, 1);
+        later.next_attempt_at = Local::now() + TimeDelta::seconds(60);
+        let queue = deliver(config(10), vec![later]).await;
+        assert_eq!(queue[0].attempts, 1);
+    }
+
+    #[tokio::test]
+    async fn drops_a_delivery_after_max_attempts() {
+        let queue = vec![delivery(UNREACHABLE, 1), delivery(UNREACHABLE, 2)];
+        let queue = deliver(config(3), queue).await;
+        assert_eq!(queue.len(), 1);
+        assert_eq!(queue[0].attempts, 2);
+    }
+
+    #[tokio::test]
+    async fn drops_a_delivery_to_a_removed_endpoint() {
+        let queue = deliver(config(10), vec![delivery("http://127.0.0.1:9/removed", 0)]).await;
+        assert!(queue.is_empty());
+    }
+}